@@ -0,0 +1,776 @@
+//! Helpers for validating and defaulting fields of the adapter configuration JSON.
+
+use zed_extension_api::serde_json::{self, Value};
+
+const UTF8_BOM: char = '\u{feff}';
+
+/// Environment variables that fill in config fields the debug configuration leaves unset.
+/// Precedence is config JSON, then these variables, then built-in defaults.
+const ENV_OVERRIDES: [(&str, &str, EnvValueKind); 4] = [
+    ("AHK_DEBUG_PORT", "port", EnvValueKind::Port),
+    ("AHK_DEBUG_TRACE", "trace", EnvValueKind::String),
+    ("AHK_DEBUG_VERSION", "adapterVersion", EnvValueKind::String),
+    ("AHK_DEBUG_REPO", "adapterRepository", EnvValueKind::String),
+];
+
+#[derive(Clone, Copy)]
+enum EnvValueKind {
+    Port,
+    String,
+}
+
+/// Parses debug configuration text, ignoring a leading UTF-8 BOM that some editors add.
+///
+/// Configs copied from docs often contain `//` comments or trailing commas. Those are
+/// only accepted when the config itself opts in with `"allowComments": true`.
+///
+/// An empty or whitespace-only config is treated as `{}`, so every field takes its default.
+pub(crate) fn parse(text: &str) -> Result<Value, String> {
+    let text = text.strip_prefix(UTF8_BOM).unwrap_or(text);
+    if text.trim().is_empty() {
+        return Ok(Value::Object(Default::default()));
+    }
+
+    let strict_err = match serde_json::from_str(text) {
+        Ok(config) => return Ok(config),
+        Err(e) => e,
+    };
+
+    match serde_json::from_str::<Value>(&strip_jsonc(text)) {
+        Ok(config) if config.get("allowComments") == Some(&Value::Bool(true)) => Ok(config),
+        Ok(_) => Err(format!(
+            "{} (the config contains comments or trailing commas; set \"allowComments\": true to accept them)",
+            strict_err
+        )),
+        Err(_) => Err(strict_err.to_string()),
+    }
+}
+
+/// Fills fields missing from `config` with values from [`ENV_OVERRIDES`]. Empty
+/// variables are ignored.
+pub(crate) fn apply_env_overrides(
+    config: &mut Value,
+    env_lookup: impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    for (name, field, kind) in ENV_OVERRIDES {
+        if !matches!(config.get(field), None | Some(Value::Null)) {
+            continue;
+        }
+        let Some(value) = env_lookup(name).filter(|value| !value.trim().is_empty()) else {
+            continue;
+        };
+
+        config[field] = match kind {
+            EnvValueKind::Port => value
+                .trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port > 0)
+                .map(|port| serde_json::json!(port))
+                .ok_or_else(|| {
+                    format!("Invalid {} value '{}', expected a port number", name, value)
+                })?,
+            EnvValueKind::String => Value::String(value),
+        };
+    }
+    Ok(())
+}
+
+/// How the injected default `port` combines with one the user already set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MergeStrategy {
+    /// Only fill in a port the user left unset.
+    PreserveUser,
+    /// Always write the extension's port, replacing the user's.
+    ForceDefault,
+}
+
+impl MergeStrategy {
+    /// Reads `portMergeStrategy`, defaulting to [`MergeStrategy::PreserveUser`].
+    pub(crate) fn from_config(config: &Value) -> Result<Self, String> {
+        match config.get("portMergeStrategy") {
+            None | Some(Value::Null) => Ok(Self::PreserveUser),
+            Some(Value::String(value)) if value == "preserveUser" => Ok(Self::PreserveUser),
+            Some(Value::String(value)) if value == "forceDefault" => Ok(Self::ForceDefault),
+            Some(other) => Err(format!(
+                "Invalid 'portMergeStrategy' value {}, expected one of 'preserveUser', 'forceDefault'",
+                other
+            )),
+        }
+    }
+}
+
+/// Writes `value` into `field` according to `strategy`.
+pub(crate) fn inject_default(
+    config: &mut Value,
+    field: &str,
+    value: Value,
+    strategy: MergeStrategy,
+) {
+    let is_unset = matches!(config.get(field), None | Some(Value::Null));
+    if is_unset || strategy == MergeStrategy::ForceDefault {
+        config[field] = value;
+    }
+}
+
+/// Removes `//` and `/* */` comments and trailing commas outside of string literals.
+fn strip_jsonc(text: &str) -> String {
+    let mut without_comments = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            without_comments.push(c);
+            match c {
+                '\\' => without_comments.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                without_comments.push(c);
+            }
+            ('/', Some('/')) => {
+                // Skip to the end of the line, keeping the newline itself
+                while chars.next_if(|&next| next != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = '\0';
+                for next in chars.by_ref() {
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
+                }
+            }
+            _ => without_comments.push(c),
+        }
+    }
+
+    let mut result = String::with_capacity(without_comments.len());
+    let mut chars = without_comments.chars().peekable();
+    in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            result.push(c);
+            match c {
+                '\\' => result.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        if c == ',' {
+            let rest = chars.clone().find(|next| !next.is_whitespace());
+            if matches!(rest, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        in_string = c == '"';
+        result.push(c);
+    }
+
+    result
+}
+
+fn quoted_list(values: &[&str]) -> String {
+    values
+        .iter()
+        .map(|v| format!("'{}'", v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Validates that `field` is one of `allowed`, inserting `default` when it is absent.
+pub(crate) fn enum_field(
+    config: &mut Value,
+    field: &str,
+    allowed: &[&str],
+    default: Option<&str>,
+) -> Result<(), String> {
+    match config.get(field) {
+        None | Some(Value::Null) => {
+            if let Some(default) = default {
+                config[field] = serde_json::json!(default);
+            }
+            Ok(())
+        }
+        Some(Value::String(value)) if allowed.contains(&value.as_str()) => Ok(()),
+        Some(other) => Err(format!(
+            "Invalid '{}' value {}, expected one of {}",
+            field,
+            other,
+            quoted_list(allowed)
+        )),
+    }
+}
+
+/// Reads an optional boolean `field`, returning `default` when it is absent.
+pub(crate) fn bool_field(config: &Value, field: &str, default: bool) -> Result<bool, String> {
+    match config.get(field) {
+        None | Some(Value::Null) => Ok(default),
+        Some(Value::Bool(value)) => Ok(*value),
+        Some(other) => Err(format!(
+            "Invalid '{}' value {}, expected true or false",
+            field, other
+        )),
+    }
+}
+
+/// Validates an optional boolean `field` and writes `default` into the config when absent.
+pub(crate) fn inject_bool_field(
+    config: &mut Value,
+    field: &str,
+    default: bool,
+) -> Result<bool, String> {
+    let value = bool_field(config, field, default)?;
+    config[field] = Value::Bool(value);
+    Ok(value)
+}
+
+/// Validates that an optional `field` is an object whose values are all booleans.
+pub(crate) fn bool_object_field(config: &Value, field: &str) -> Result<(), String> {
+    let object = match config.get(field) {
+        None | Some(Value::Null) => return Ok(()),
+        Some(Value::Object(object)) => object,
+        Some(other) => {
+            return Err(format!(
+                "Invalid '{}' value {}, expected an object of true/false flags",
+                field, other
+            ))
+        }
+    };
+
+    match object.iter().find(|(_, value)| !value.is_boolean()) {
+        Some((key, value)) => Err(format!(
+            "Invalid '{}.{}' value {}, expected true or false",
+            field, key, value
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Validates that an optional `field` is an array of non-empty strings.
+pub(crate) fn non_empty_strings_field(config: &Value, field: &str) -> Result<(), String> {
+    let items = match config.get(field) {
+        None | Some(Value::Null) => return Ok(()),
+        Some(Value::Array(items)) => items,
+        Some(other) => {
+            return Err(format!(
+                "Invalid '{}' value {}, expected an array of strings",
+                field, other
+            ))
+        }
+    };
+
+    match items
+        .iter()
+        .position(|item| item.as_str().is_none_or(|s| s.trim().is_empty()))
+    {
+        Some(index) => Err(format!(
+            "Invalid '{}[{}]' value {}, expected a non-empty string",
+            field, index, items[index]
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Reads an optional `field` that must be a positive integer when present.
+pub(crate) fn positive_int_field(config: &Value, field: &str) -> Result<Option<u64>, String> {
+    match config.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().filter(|n| *n > 0).map(Some).ok_or_else(|| {
+            format!(
+                "Invalid '{}' value {}, expected a positive integer",
+                field, value
+            )
+        }),
+    }
+}
+
+/// Reads an optional `field` that must be a non-negative integer when present.
+pub(crate) fn non_negative_int_field(config: &Value, field: &str) -> Result<Option<u64>, String> {
+    match config.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+            format!(
+                "Invalid '{}' value {}, expected a non-negative integer",
+                field, value
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: [&str; 3] = ["off", "messages", "verbose"];
+
+    // ==================== parse tests ====================
+
+    #[test]
+    fn parse_accepts_bom_prefixed_json() {
+        // Arrange
+        let text = "\u{feff}{\"request\": \"launch\", \"program\": \"main.ahk\"}";
+
+        // Act
+        let result = parse(text);
+
+        // Assert
+        let config = result.unwrap();
+        assert_eq!(config["request"], "launch");
+        assert_eq!(config["program"], "main.ahk");
+    }
+
+    #[test]
+    fn parse_treats_blank_config_as_empty_object() {
+        // Arrange
+        let text = "\u{feff} \r\n";
+
+        // Act
+        let result = parse(text);
+
+        // Assert
+        assert_eq!(result, Ok(serde_json::json!({})));
+    }
+
+    #[test]
+    fn parse_keeps_bom_inside_string_values() {
+        // Arrange
+        let text = "{\"label\": \"\u{feff}Debug\"}";
+
+        // Act
+        let result = parse(text);
+
+        // Assert
+        assert_eq!(result.unwrap()["label"], "\u{feff}Debug");
+    }
+
+    #[test]
+    fn parse_accepts_jsonc_when_allow_comments_is_set() {
+        // Arrange
+        let text = r#"{
+            // Copied from the README
+            "allowComments": true,
+            "program": "C:/scripts/main.ahk", /* entry point */
+            "args": ["--url", "http://example.com",],
+        }"#;
+
+        // Act
+        let result = parse(text);
+
+        // Assert
+        let config = result.unwrap();
+        assert_eq!(config["program"], "C:/scripts/main.ahk");
+        assert_eq!(
+            config["args"],
+            serde_json::json!(["--url", "http://example.com"])
+        );
+    }
+
+    #[test]
+    fn parse_rejects_jsonc_without_allow_comments() {
+        // Arrange
+        let text = r#"{
+            // Copied from the README
+            "program": "C:/scripts/main.ahk",
+        }"#;
+
+        // Act
+        let result = parse(text);
+
+        // Assert
+        assert!(result.unwrap_err().contains("allowComments"));
+    }
+
+    #[test]
+    fn strip_jsonc_keeps_comment_markers_inside_strings() {
+        // Arrange
+        let text = r#"{"pattern": "a // b /* c */", "escaped": "say \"hi\", // ok",}"#;
+
+        // Act
+        let result = strip_jsonc(text);
+
+        // Assert
+        assert_eq!(
+            result,
+            r#"{"pattern": "a // b /* c */", "escaped": "say \"hi\", // ok"}"#
+        );
+    }
+
+    // ==================== apply_env_overrides tests ====================
+
+    #[test]
+    fn apply_env_overrides_fills_missing_fields() {
+        // Arrange
+        let mut config = serde_json::json!({"program": "main.ahk"});
+        let env_lookup = |name: &str| match name {
+            "AHK_DEBUG_PORT" => Some("9100".to_string()),
+            "AHK_DEBUG_TRACE" => Some("verbose".to_string()),
+            "AHK_DEBUG_VERSION" => Some("1.1.0".to_string()),
+            "AHK_DEBUG_REPO" => Some("acme/autohotkey-debug-adapter".to_string()),
+            _ => None,
+        };
+
+        // Act
+        let result = apply_env_overrides(&mut config, env_lookup);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["port"], 9100);
+        assert_eq!(config["trace"], "verbose");
+        assert_eq!(config["adapterVersion"], "1.1.0");
+        assert_eq!(config["adapterRepository"], "acme/autohotkey-debug-adapter");
+    }
+
+    #[test]
+    fn apply_env_overrides_keeps_explicit_config() {
+        // Arrange
+        let mut config =
+            serde_json::json!({"port": 9200, "trace": "off", "adapterVersion": "1.2.0"});
+        let env_lookup = |name: &str| match name {
+            "AHK_DEBUG_PORT" => Some("9100".to_string()),
+            "AHK_DEBUG_TRACE" => Some("verbose".to_string()),
+            "AHK_DEBUG_VERSION" => Some("1.1.0".to_string()),
+            _ => None,
+        };
+
+        // Act
+        let result = apply_env_overrides(&mut config, env_lookup);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["port"], 9200);
+        assert_eq!(config["trace"], "off");
+        assert_eq!(config["adapterVersion"], "1.2.0");
+    }
+
+    #[test]
+    fn apply_env_overrides_rejects_invalid_port() {
+        // Arrange
+        let mut config = serde_json::json!({});
+        let env_lookup = |name: &str| (name == "AHK_DEBUG_PORT").then(|| "90000".to_string());
+
+        // Act
+        let result = apply_env_overrides(&mut config, env_lookup);
+
+        // Assert
+        assert!(result.unwrap_err().contains("AHK_DEBUG_PORT"));
+    }
+
+    // ==================== MergeStrategy tests ====================
+
+    #[test]
+    fn merge_strategy_defaults_to_preserve_user() {
+        // Arrange
+        let config = serde_json::json!({});
+
+        // Act
+        let result = MergeStrategy::from_config(&config);
+
+        // Assert
+        assert_eq!(result, Ok(MergeStrategy::PreserveUser));
+    }
+
+    #[test]
+    fn merge_strategy_rejects_unknown_value() {
+        // Arrange
+        let config = serde_json::json!({"portMergeStrategy": "replace"});
+
+        // Act
+        let result = MergeStrategy::from_config(&config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'portMergeStrategy'"));
+    }
+
+    #[test]
+    fn inject_default_preserve_user_keeps_user_value() {
+        // Arrange
+        let mut config = serde_json::json!({"port": 9100});
+
+        // Act
+        inject_default(
+            &mut config,
+            "port",
+            serde_json::json!(9005),
+            MergeStrategy::PreserveUser,
+        );
+
+        // Assert
+        assert_eq!(config["port"], 9100);
+    }
+
+    #[test]
+    fn inject_default_force_default_overwrites_user_value() {
+        // Arrange
+        let mut config = serde_json::json!({"port": 9100});
+
+        // Act
+        inject_default(
+            &mut config,
+            "port",
+            serde_json::json!(9005),
+            MergeStrategy::ForceDefault,
+        );
+
+        // Assert
+        assert_eq!(config["port"], 9005);
+    }
+
+    // ==================== enum_field tests ====================
+
+    #[test]
+    fn enum_field_inserts_default_when_missing() {
+        // Arrange
+        let mut config = serde_json::json!({});
+
+        // Act
+        let result = enum_field(&mut config, "trace", &LEVELS, Some("off"));
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["trace"], "off");
+    }
+
+    #[test]
+    fn enum_field_leaves_missing_field_without_default() {
+        // Arrange
+        let mut config = serde_json::json!({});
+
+        // Act
+        let result = enum_field(&mut config, "trace", &LEVELS, None);
+
+        // Assert
+        assert!(result.is_ok());
+        assert!(config.get("trace").is_none());
+    }
+
+    #[test]
+    fn enum_field_rejects_non_string_value() {
+        // Arrange
+        let mut config = serde_json::json!({"trace": true});
+
+        // Act
+        let result = enum_field(&mut config, "trace", &LEVELS, Some("off"));
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("'trace'"));
+    }
+
+    // ==================== bool_field tests ====================
+
+    #[test]
+    fn bool_field_returns_default_when_missing() {
+        // Arrange
+        let config = serde_json::json!({});
+
+        // Act
+        let result = bool_field(&config, "restart", true);
+
+        // Assert
+        assert_eq!(result, Ok(true));
+    }
+
+    #[test]
+    fn bool_field_returns_supplied_value() {
+        // Arrange
+        let config = serde_json::json!({"restart": false});
+
+        // Act
+        let result = bool_field(&config, "restart", true);
+
+        // Assert
+        assert_eq!(result, Ok(false));
+    }
+
+    #[test]
+    fn bool_field_rejects_non_boolean() {
+        // Arrange
+        let config = serde_json::json!({"restart": "yes"});
+
+        // Act
+        let result = bool_field(&config, "restart", false);
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("'restart'"));
+    }
+
+    // ==================== inject_bool_field tests ====================
+
+    #[test]
+    fn inject_bool_field_writes_default_when_missing() {
+        // Arrange
+        let mut config = serde_json::json!({});
+
+        // Act
+        let result = inject_bool_field(&mut config, "restart", false);
+
+        // Assert
+        assert_eq!(result, Ok(false));
+        assert_eq!(config["restart"], false);
+    }
+
+    #[test]
+    fn inject_bool_field_keeps_supplied_value() {
+        // Arrange
+        let mut config = serde_json::json!({"restart": true});
+
+        // Act
+        let result = inject_bool_field(&mut config, "restart", false);
+
+        // Assert
+        assert_eq!(result, Ok(true));
+        assert_eq!(config["restart"], true);
+    }
+
+    // ==================== bool_object_field tests ====================
+
+    #[test]
+    fn bool_object_field_accepts_boolean_flags() {
+        // Arrange
+        let config = serde_json::json!({"capabilities": {"supportsConditionalBreakpoints": false}});
+
+        // Act
+        let result = bool_object_field(&config, "capabilities");
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn bool_object_field_rejects_non_object() {
+        // Arrange
+        let config = serde_json::json!({"capabilities": ["supportsConditionalBreakpoints"]});
+
+        // Act
+        let result = bool_object_field(&config, "capabilities");
+
+        // Assert
+        assert!(result.unwrap_err().contains("'capabilities'"));
+    }
+
+    #[test]
+    fn bool_object_field_names_non_boolean_entry() {
+        // Arrange
+        let config = serde_json::json!({"capabilities": {"supportsLogPoints": "no"}});
+
+        // Act
+        let result = bool_object_field(&config, "capabilities");
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .contains("'capabilities.supportsLogPoints'"));
+    }
+
+    // ==================== non_empty_strings_field tests ====================
+
+    #[test]
+    fn non_empty_strings_field_accepts_strings() {
+        // Arrange
+        let config = serde_json::json!({"watches": ["A_Index", "myVar.Length()"]});
+
+        // Act
+        let result = non_empty_strings_field(&config, "watches");
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn non_empty_strings_field_names_invalid_entry() {
+        // Arrange
+        let config = serde_json::json!({"watches": ["A_Index", " "]});
+
+        // Act
+        let result = non_empty_strings_field(&config, "watches");
+
+        // Assert
+        assert!(result.unwrap_err().contains("'watches[1]'"));
+    }
+
+    // ==================== positive_int_field tests ====================
+
+    #[test]
+    fn positive_int_field_returns_none_when_missing() {
+        // Arrange
+        let config = serde_json::json!({});
+
+        // Act
+        let result = positive_int_field(&config, "keepAliveMs");
+
+        // Assert
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn positive_int_field_returns_supplied_value() {
+        // Arrange
+        let config = serde_json::json!({"keepAliveMs": 5000});
+
+        // Act
+        let result = positive_int_field(&config, "keepAliveMs");
+
+        // Assert
+        assert_eq!(result, Ok(Some(5000)));
+    }
+
+    #[test]
+    fn positive_int_field_rejects_zero_negative_and_non_integer() {
+        for value in [
+            serde_json::json!(0),
+            serde_json::json!(-5),
+            serde_json::json!(1.5),
+            serde_json::json!("1000"),
+        ] {
+            // Arrange
+            let config = serde_json::json!({ "keepAliveMs": value });
+
+            // Act
+            let result = positive_int_field(&config, "keepAliveMs");
+
+            // Assert
+            assert!(result.is_err(), "expected {} to be rejected", value);
+        }
+    }
+
+    // ==================== non_negative_int_field tests ====================
+
+    #[test]
+    fn non_negative_int_field_accepts_zero() {
+        // Arrange
+        let config = serde_json::json!({"codePage": 0});
+
+        // Act
+        let result = non_negative_int_field(&config, "codePage");
+
+        // Assert
+        assert_eq!(result, Ok(Some(0)));
+    }
+
+    #[test]
+    fn non_negative_int_field_rejects_negative_and_non_integer() {
+        for value in [serde_json::json!(-1), serde_json::json!("65001")] {
+            // Arrange
+            let config = serde_json::json!({ "codePage": value });
+
+            // Act
+            let result = non_negative_int_field(&config, "codePage");
+
+            // Assert
+            assert!(result.is_err(), "expected {} to be rejected", value);
+        }
+    }
+}
@@ -1,525 +1,8895 @@
-use std::{env, path::Path, sync::OnceLock};
+mod config;
+mod paths;
+mod script;
+
+use std::{
+    env, fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use zed_extension_api::{
-    self as zed, download_file, latest_github_release, serde_json, DebugAdapterBinary, DebugConfig,
-    DebugRequest, DebugScenario, DebugTaskDefinition, DownloadedFileType, GithubReleaseAsset,
-    GithubReleaseOptions, StartDebuggingRequestArguments, StartDebuggingRequestArgumentsRequest,
-    Worktree,
+    self as zed, download_file, github_release_by_tag_name, latest_github_release, serde_json,
+    DebugAdapterBinary, DebugConfig, DebugRequest, DebugScenario, DebugTaskDefinition,
+    DownloadedFileType, EnvVars, GithubRelease, GithubReleaseAsset, GithubReleaseOptions,
+    StartDebuggingRequestArguments, StartDebuggingRequestArgumentsRequest, TcpArguments,
+    TcpArgumentsTemplate, Worktree,
 };
 
-const ADAPTER_NAME: &str = "autohotkey";
-const GITHUB_REPO: &str = "alfredomtx/autohotkey-debug-adapter";
+/// Adapter name registered in `extension.toml` and used in `.zed/debug.json`.
+pub(crate) const ADAPTER_NAME: &str = "autohotkey";
+/// GitHub repository the debug adapter releases are downloaded from.
+pub(crate) const GITHUB_REPO: &str = "alfredomtx/autohotkey-debug-adapter";
+const AHK_EXE_SUBPATH: &str = "extension/bin/AutoHotkey.exe";
+const ADAPTER_SCRIPT_SUBPATH: &str = "extension/ahkdbg/debugAdapter.ahk";
+/// Port the adapter listens on when the config doesn't set one. Can be overridden at
+/// build time with `AHK_DEFAULT_PORT=<port> cargo build`.
+const DEFAULT_PORT: u16 = match option_env!("AHK_DEFAULT_PORT") {
+    Some(port) => parse_port(port),
+    None => 9005,
+};
+const INSTALL_SENTINEL: &str = ".installed";
+const WORKTREE_SCOPES_DIR: &str = "worktrees";
+/// Installs from an `adapterRepository` other than [`GITHUB_REPO`] live under here.
+const REPOSITORY_SCOPES_DIR: &str = "repositories";
+const TEMP_SCRIPTS_DIR: &str = "temp_scripts";
+const TEMP_SCRIPT_NAME: &str = "script.ahk";
+const SESSION_DIR_PREFIX: &str = "session_";
+const STALE_SESSION_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+const DEFAULT_CACHE_DISCOVERY_ATTEMPTS: u32 = 3;
+const DEFAULT_MAX_CACHED_VERSIONS: usize = 3;
+const CACHE_DISCOVERY_RETRY_DELAY: Duration = Duration::from_millis(100);
+const SECRET_ENV_PATTERNS: [&str; 5] = ["TOKEN", "SECRET", "PASSWORD", "PASSWD", "API_KEY"];
+const MASKED_ENV_VALUE: &str = "********";
+/// Shell environment variables included in diagnostics. Anything else may hold
+/// credentials or personal data, so it is left out.
+const DIAGNOSTIC_ENV_KEYS: [&str; 10] = [
+    "PATH",
+    "PATHEXT",
+    "TEMP",
+    "TMP",
+    "HOME",
+    "USERPROFILE",
+    "LOCALAPPDATA",
+    "ProgramFiles",
+    "AHK_DEBUG_PORT",
+    "AHK_DEBUG_TRACE",
+];
+/// Config keys only the extension reads, to install the adapter or build its command
+/// line. They are left out of the configuration sent to the adapter.
+const EXTENSION_ONLY_KEYS: [&str; 34] = [
+    "defaultRequest",
+    "allowedRequests",
+    "allowComments",
+    "programSearchPaths",
+    "tempScriptMaxAgeMs",
+    "validateCwd",
+    "createCwd",
+    "runtimeExecutable",
+    "useSystemRuntime",
+    "skipBinaryValidation",
+    "runtimeMap",
+    "exeName",
+    "exeSubpath",
+    "scriptSubpath",
+    "bundledRuntime",
+    "portEnv",
+    "portMergeStrategy",
+    "transport",
+    "stopOnEntryFallback",
+    "libPath",
+    "dataDir",
+    "networkTimeoutMs",
+    "maxDownloadSizeBytes",
+    "adapterNameMatching",
+    "cacheDiscoveryAttempts",
+    "resolutionOrder",
+    "assetName",
+    "maxCachedVersions",
+    "allowInsecureDownloads",
+    "scopeInstallToWorktree",
+    "userAgentSuffix",
+    "adapterVersion",
+    "adapterRepository",
+    "proxy",
+];
+const TRACE_LEVELS: [&str; 3] = ["off", "messages", "verbose"];
+const SCRIPT_ENCODINGS: [&str; 3] = ["utf-8", "utf-16", "ansi"];
+const OUTPUT_CAPTURE_MODES: [&str; 2] = ["console", "std"];
+const EXCEPTION_BREAK_MODES: [&str; 3] = ["never", "unhandled", "always"];
+const RUNTIME_VERSIONS: [&str; 2] = ["v1", "v2"];
+/// Standard AutoHotkey install directories, as (environment variable, fallback root,
+/// subdirectory), searched in order.
+const SYSTEM_RUNTIME_DIRS: [(&str, Option<&str>, &str); 3] = [
+    ("ProgramFiles", Some("C:\\Program Files"), "AutoHotkey"),
+    (
+        "ProgramFiles(x86)",
+        Some("C:\\Program Files (x86)"),
+        "AutoHotkey",
+    ),
+    ("LOCALAPPDATA", None, "Programs\\AutoHotkey"),
+];
+const SYSTEM_RUNTIME_EXES: [&str; 3] = ["AutoHotkey.exe", "AutoHotkeyU64.exe", "AutoHotkeyU32.exe"];
+/// Runtime file names looked for in the adapter's `bin` folder. `AutoHotkey.exe`, the
+/// [`DEFAULT_RUNTIME_VERSION`] build the adapter script is written for, comes first.
+const BUNDLED_RUNTIME_EXES: [&str; 3] = ["AutoHotkey.exe", "AutoHotkey64.exe", "AutoHotkey32.exe"];
+/// Oldest AutoHotkey build known to honor `stopOnEntry`.
+const STOP_ON_ENTRY_MIN_RUNTIME: [u64; 3] = [1, 1, 37];
+/// The bundled runtime is AutoHotkey v1.
+const DEFAULT_RUNTIME_VERSION: &str = "v1";
+const TRANSPORTS: [&str; 2] = ["stdio", "tcp"];
+const ERROR_STDOUT_FLAG: &str = "/ErrorStdOut";
+const DEFAULT_PROBE_INTERVAL_MS: u64 = 100;
+const DEFAULT_PROBE_MAX_ATTEMPTS: u64 = 50;
+const WORKSPACE_FOLDER_VAR: &str = "${workspaceFolder}";
+/// Environment variable the adapter reads to locate AutoHotkey libraries.
+const LIB_PATH_ENV: &str = "AHK_LIB";
+/// Environment variable the adapter reads to locate the directory for AutoHotkey state.
+const DATA_DIR_ENV: &str = "AHK_DATA_DIR";
+const FILE_DIRNAME_VAR: &str = "${fileDirname}";
+const REMOVE_DIR_ATTEMPTS: u32 = 3;
+const REMOVE_DIR_RETRY_DELAY: Duration = Duration::from_millis(200);
+/// Suffix of the directory a download is extracted into before it replaces the install.
+const STAGING_SUFFIX: &str = ".partial";
+const USER_AGENT_PRODUCT: &str = "zed-autohotkey-debugger";
+/// Token turned into an `Authorization` header when `requestHeaders` doesn't set one.
+const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+const PROXY_ENV_VARS: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"];
 
-fn request_type_from_config(
-    config: &serde_json::Value,
-) -> Result<StartDebuggingRequestArgumentsRequest, String> {
-    match config.get("request").and_then(|v| v.as_str()) {
-        Some("launch") => Ok(StartDebuggingRequestArgumentsRequest::Launch),
-        Some("attach") => Ok(StartDebuggingRequestArgumentsRequest::Attach),
-        Some(other) => Err(format!(
-            "Invalid request type '{}', expected 'launch' or 'attach'",
-            other
-        )),
-        None => Ok(StartDebuggingRequestArgumentsRequest::Launch),
+/// Timeouts and size caps, parsed once from the config with defaults for missing fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Limits {
+    /// How long fetching and downloading the adapter may take before failures are
+    /// reported as a slow or blocked network. Zed owns the requests, so they can't be
+    /// cancelled from here.
+    network_timeout: Duration,
+    /// How long the adapter waits for Zed to connect.
+    connect_timeout: Duration,
+    /// Largest extracted adapter install that is kept.
+    max_download_size: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            network_timeout: Duration::from_secs(60),
+            connect_timeout: Duration::from_secs(10),
+            max_download_size: 200 * 1024 * 1024,
+        }
     }
 }
 
-fn validate_adapter_name(name: &str) -> Result<(), String> {
-    if name != ADAPTER_NAME {
-        return Err(format!(
-            "Unsupported adapter '{}', expected '{}'",
-            name, ADAPTER_NAME
-        ));
+impl Limits {
+    fn from_config(config: &serde_json::Value) -> Result<Self, String> {
+        let defaults = Self::default();
+        let millis = |field| {
+            config::positive_int_field(config, field).map(|ms| ms.map(Duration::from_millis))
+        };
+        Ok(Self {
+            network_timeout: millis("networkTimeoutMs")?.unwrap_or(defaults.network_timeout),
+            connect_timeout: millis("connectTimeoutMs")?.unwrap_or(defaults.connect_timeout),
+            max_download_size: config::positive_int_field(config, "maxDownloadSizeBytes")?
+                .unwrap_or(defaults.max_download_size),
+        })
     }
-    Ok(())
 }
 
-struct AutoHotkeyDebugger {
-    cached_version: OnceLock<String>,
+/// Options that control how the debug adapter is fetched and installed.
+#[derive(Debug)]
+struct InstallOptions {
+    /// The `proxy` option, or the proxy from the shell environment.
+    proxy: Option<String>,
+    cache_discovery_attempts: u32,
+    scope_to_worktree: bool,
+    user_agent_suffix: Option<String>,
+    /// Names of the headers from [`request_headers`]; values are not kept since they
+    /// may hold tokens.
+    request_header_names: Vec<String>,
+    asset_name: Option<String>,
+    max_cached_versions: usize,
+    /// Use a complete cached install before looking up the latest release.
+    cache_first: bool,
+    allow_insecure_downloads: bool,
+    /// Trust the version already installed this session without re-checking its files
+    /// (`skipBinaryValidation`).
+    skip_validation: bool,
+    /// Where a fresh install must contain the adapter script, relative to the install.
+    script_subpath: String,
+    /// Release to install instead of the latest (`adapterVersion`).
+    version: Option<String>,
+    /// Repository to install from instead of [`GITHUB_REPO`] (`adapterRepository`).
+    repository: Option<String>,
+    limits: Limits,
 }
 
-impl AutoHotkeyDebugger {
-    fn adapter_dir(&self) -> String {
-        env::current_dir()
-            .unwrap()
-            .join(ADAPTER_NAME)
-            .to_string_lossy()
-            .into_owned()
+impl Default for InstallOptions {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            cache_discovery_attempts: DEFAULT_CACHE_DISCOVERY_ATTEMPTS,
+            scope_to_worktree: false,
+            user_agent_suffix: None,
+            request_header_names: Vec::new(),
+            asset_name: None,
+            max_cached_versions: DEFAULT_MAX_CACHED_VERSIONS,
+            cache_first: false,
+            allow_insecure_downloads: false,
+            skip_validation: false,
+            script_subpath: ADAPTER_SCRIPT_SUBPATH.to_owned(),
+            version: None,
+            repository: None,
+            limits: Limits::default(),
+        }
     }
+}
 
-    fn versioned_dir(&self, version: &str) -> String {
-        format!("{}/{}_{}", self.adapter_dir(), ADAPTER_NAME, version)
+impl InstallOptions {
+    /// Reads the install options, rejecting invalid values before anything is
+    /// downloaded or removed. `env_lookup` reads the environment overrides.
+    fn from_config(
+        config: &serde_json::Value,
+        env_lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, String> {
+        let mut config = config.clone();
+        config::apply_env_overrides(&mut config, &env_lookup)?;
+        let config = &config;
+        let defaults = Self::default();
+        Ok(Self {
+            proxy: detect_proxy(config.get("proxy").and_then(|v| v.as_str()), &env_lookup),
+            cache_discovery_attempts: config
+                .get("cacheDiscoveryAttempts")
+                .and_then(|v| v.as_u64())
+                .and_then(|n| u32::try_from(n).ok())
+                .filter(|n| *n >= 1)
+                .unwrap_or(defaults.cache_discovery_attempts),
+            scope_to_worktree: config
+                .get("scopeInstallToWorktree")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(defaults.scope_to_worktree),
+            user_agent_suffix: config
+                .get("userAgentSuffix")
+                .and_then(|v| v.as_str())
+                .filter(|suffix| !suffix.trim().is_empty())
+                .map(ToOwned::to_owned),
+            request_header_names: request_headers(config, &env_lookup)?
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+            asset_name: config
+                .get("assetName")
+                .and_then(|v| v.as_str())
+                .filter(|name| !name.trim().is_empty())
+                .map(ToOwned::to_owned),
+            max_cached_versions: config::positive_int_field(config, "maxCachedVersions")?
+                .map_or(defaults.max_cached_versions, |n| {
+                    usize::try_from(n).unwrap_or(usize::MAX)
+                }),
+            cache_first: config.get("resolutionOrder").and_then(|v| v.as_str())
+                == Some("cache-first"),
+            allow_insecure_downloads: config::bool_field(
+                config,
+                "allowInsecureDownloads",
+                defaults.allow_insecure_downloads,
+            )?,
+            skip_validation: config::bool_field(
+                config,
+                "skipBinaryValidation",
+                defaults.skip_validation,
+            )?,
+            script_subpath: config
+                .get("scriptSubpath")
+                .and_then(|v| v.as_str())
+                .filter(|subpath| !subpath.trim().is_empty() && Path::new(subpath).is_relative())
+                .map_or(defaults.script_subpath, ToOwned::to_owned),
+            version: pinned_version(config)?,
+            repository: adapter_repository(config)?,
+            limits: Limits::from_config(config)?,
+        })
     }
+}
 
-    fn fetch_latest_release() -> Result<(GithubReleaseAsset, String), String> {
-        let release = latest_github_release(
-            GITHUB_REPO,
-            GithubReleaseOptions {
-                require_assets: true,
-                pre_release: false,
-            },
-        )?;
-
-        let version = release.version.trim_start_matches('v').to_string();
-        let expected_name = format!("autohotkey-debug-{}.vsix", version);
+/// Parses a port number at compile time; invalid values fail the build.
+const fn parse_port(value: &str) -> u16 {
+    let bytes = value.as_bytes();
+    assert!(!bytes.is_empty(), "AHK_DEFAULT_PORT must not be empty");
+    let mut port: u32 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(
+            bytes[i].is_ascii_digit(),
+            "AHK_DEFAULT_PORT must be a number"
+        );
+        port = port * 10 + (bytes[i] - b'0') as u32;
+        assert!(
+            port <= u16::MAX as u32,
+            "AHK_DEFAULT_PORT must be a valid port"
+        );
+        i += 1;
+    }
+    assert!(port > 0, "AHK_DEFAULT_PORT must be a valid port");
+    port as u16
+}
 
-        let asset = release
-            .assets
-            .into_iter()
-            .find(|a| a.name.ends_with(".vsix"))
-            .ok_or_else(|| {
-                format!(
-                    "No .vsix asset found in release (expected {})",
-                    expected_name
-                )
-            })?;
+/// Returns a stable directory name for a worktree root (64-bit FNV-1a, hex encoded).
+/// `DefaultHasher` is avoided because its output may change between Rust releases.
+fn worktree_scope(worktree_root: &str) -> String {
+    let hash = worktree_root
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    format!("{:016x}", hash)
+}
 
-        Ok((asset, version))
+/// Identifies this extension in diagnostics. Zed's GitHub APIs don't accept custom
+/// headers, so this can't be sent as the request's User-Agent; it is reported in
+/// fetch errors instead so throttled or blocked requests can be traced to a version.
+fn user_agent(suffix: Option<&str>) -> String {
+    let base = format!("{}/{}", USER_AGENT_PRODUCT, env!("CARGO_PKG_VERSION"));
+    match suffix {
+        Some(suffix) => format!("{} {}", base, suffix.trim()),
+        None => base,
     }
+}
 
-    fn ensure_adapter_installed(&mut self) -> Result<String, String> {
-        if let Some(version) = self.cached_version.get() {
-            return Ok(version.clone());
+/// Assembles the headers configured for GitHub requests: the `requestHeaders` entries,
+/// plus `Authorization: Bearer $GITHUB_TOKEN` unless one is set there already.
+fn request_headers(
+    config: &serde_json::Value,
+    env_lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut headers = match config.get("requestHeaders") {
+        None => Vec::new(),
+        Some(serde_json::Value::Object(map)) => map
+            .iter()
+            .map(|(name, value)| match value.as_str() {
+                Some(value) => Ok((name.clone(), value.to_owned())),
+                None => Err(format!(
+                    "Invalid 'requestHeaders' value for '{}', expected a string",
+                    name
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(_) => {
+            return Err("'requestHeaders' must be an object of header names to values".into())
         }
+    };
 
-        match Self::fetch_latest_release() {
-            Ok((asset, version)) => {
-                let versioned_dir = self.versioned_dir(&version);
+    let has_authorization = headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("authorization"));
+    if !has_authorization {
+        if let Some(token) = env_lookup(GITHUB_TOKEN_ENV).filter(|token| !token.trim().is_empty()) {
+            headers.push(("Authorization".into(), format!("Bearer {}", token.trim())));
+        }
+    }
+    Ok(headers)
+}
 
-                if !Path::new(&versioned_dir).exists() {
-                    let adapter_dir = self.adapter_dir();
-                    std::fs::remove_dir_all(&adapter_dir).ok();
-                    std::fs::create_dir_all(&adapter_dir)
-                        .map_err(|e| format!("Failed to create adapter directory: {}", e))?;
+/// Explains that headers from [`request_headers`] were not sent, since Zed performs
+/// GitHub requests for extensions and accepts no custom headers.
+fn unsent_headers_note(names: &[String]) -> Option<String> {
+    (!names.is_empty()).then(|| {
+        format!(
+            "Zed does not let extensions add headers to GitHub requests, so these were not sent: {}. \
+             For an authenticated or mirrored release host, download the adapter yourself and set 'runtimeExecutable'",
+            names.join(", ")
+        )
+    })
+}
 
-                    download_file(&asset.download_url, &versioned_dir, DownloadedFileType::Zip)?;
+/// Quotes one command-line word for `cmd.exe`/`CommandLineToArgvW` (`windows`) or a
+/// POSIX shell, leaving words that need no quoting untouched.
+fn quote_shell_arg(arg: &str, windows: bool) -> String {
+    if windows {
+        if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+            return arg.to_owned();
+        }
+        let mut quoted = String::from('"');
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                    backslashes = 0;
                 }
-
-                self.cached_version.set(version.clone()).ok();
-                Ok(version)
-            }
-            Err(fetch_err) => {
-                let prefix = format!("{}_", ADAPTER_NAME);
-                let adapter_dir = self.adapter_dir();
-
-                if let Ok(entries) = std::fs::read_dir(&adapter_dir) {
-                    let version = entries
-                        .filter_map(|e| e.ok())
-                        .filter_map(|entry| {
-                            entry
-                                .file_name()
-                                .to_string_lossy()
-                                .strip_prefix(&prefix)
-                                .map(ToOwned::to_owned)
-                        })
-                        .max();
-
-                    if let Some(v) = version {
-                        self.cached_version.set(v.clone()).ok();
-                        return Ok(v);
-                    }
+                _ => {
+                    quoted.push_str(&"\\".repeat(backslashes));
+                    backslashes = 0;
                 }
-
-                Err(format!(
-                    "Failed to fetch release and no cached version found: {}",
-                    fetch_err
-                ))
+            }
+            if c != '\\' {
+                quoted.push(c);
             }
         }
+        // Backslashes before the closing quote must be doubled so it isn't escaped
+        quoted.push_str(&"\\".repeat(backslashes * 2));
+        quoted.push('"');
+        quoted
+    } else {
+        let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+        if !arg.is_empty() && arg.chars().all(is_safe) {
+            return arg.to_owned();
+        }
+        format!("'{}'", arg.replace('\'', "'\\''"))
     }
+}
 
-    fn ahk_exe_path(&self, version: &str) -> String {
-        Path::new(&self.versioned_dir(version))
-            .join("extension/bin/AutoHotkey.exe")
-            .to_string_lossy()
-            .into_owned()
-    }
+/// Joins `command` and `arguments` into a line that can be pasted into a shell.
+fn shell_command(command: &str, arguments: &[String], windows: bool) -> String {
+    std::iter::once(command)
+        .chain(arguments.iter().map(String::as_str))
+        .map(|word| quote_shell_arg(word, windows))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-    fn adapter_script_path(&self, version: &str) -> String {
-        Path::new(&self.versioned_dir(version))
-            .join("extension/ahkdbg/debugAdapter.ahk")
-            .to_string_lossy()
-            .into_owned()
+/// Tags an error with the extension version so bug reports say which build failed.
+/// Messages that already carry the [`user_agent`] are returned unchanged.
+fn with_version(message: String) -> String {
+    let tag = user_agent(None);
+    if message.contains(&tag) {
+        return message;
     }
+    format!("{} [{}]", message, tag)
+}
 
-    fn build_binary(
-        &self,
-        version: &str,
-        config: DebugTaskDefinition,
-        user_provided_path: Option<String>,
-        worktree: &Worktree,
-    ) -> Result<DebugAdapterBinary, String> {
-        let ahk_exe = user_provided_path.unwrap_or_else(|| self.ahk_exe_path(version));
-        let adapter_script = self.adapter_script_path(version);
+/// Looks `name` up in `env`, such as the worktree's shell environment. The extension
+/// runs as its own process, so `std::env` doesn't see the variables of the user's shell.
+fn env_var(env: &[(String, String)], name: &str) -> Option<String> {
+    env.iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.clone())
+}
 
-        // Validate bundled AHK runtime exists
-        if !Path::new(&ahk_exe).exists() {
-            return Err(format!(
-                "Debug adapter AutoHotkey.exe not found at '{}'. Try reinstalling the extension.",
-                ahk_exe
-            ));
-        }
+/// Returns the proxy in effect, preferring the config value over the environment.
+fn detect_proxy(
+    config_proxy: Option<&str>,
+    env_lookup: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    config_proxy
+        .map(ToOwned::to_owned)
+        .or_else(|| PROXY_ENV_VARS.iter().find_map(|name| env_lookup(name)))
+        .filter(|proxy| !proxy.trim().is_empty())
+}
 
-        // Validate adapter script exists
-        if !Path::new(&adapter_script).exists() {
-            return Err(format!(
-                "Debug adapter script not found at '{}'. Try reinstalling the extension.",
-                adapter_script
-            ));
-        }
+/// Downloads are performed by Zed on the extension's behalf, so a proxy cannot be
+/// set from here. Point the user at Zed's own `proxy` setting instead.
+fn proxy_guidance(proxy: &str) -> String {
+    format!(
+        "A proxy is configured ({}), but GitHub requests are made by Zed. \
+         Set \"proxy\": \"{}\" in your Zed settings.json so downloads are routed through it.",
+        proxy, proxy
+    )
+}
 
-        let request = Self::parse_request_kind(&config.config)?;
+/// Explains a failed adapter download, naming the likely cause when the error from Zed
+/// matches a common network or GitHub failure.
+fn diagnose_download_error(url: &str, error: &str) -> String {
+    let lower = error.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
 
-        // Parse config to inject required fields
-        let mut config_json: serde_json::Value = serde_json::from_str(&config.config)
-            .map_err(|e| format!("Failed to parse config: {}", e))?;
+    let cause = if has(&["dns", "resolve", "lookup", "no such host"]) {
+        Some("the GitHub host name could not be resolved; check your network or DNS settings")
+    } else if has(&["tls", "ssl", "certificate"]) {
+        Some("the TLS handshake failed; a proxy or antivirus may be intercepting HTTPS traffic")
+    } else if has(&["404", "not found"]) {
+        Some("the release asset was not found (404); it may have been moved or deleted")
+    } else if has(&["403", "rate limit", "forbidden"]) {
+        Some("GitHub refused the request (403), most likely because of API rate limiting; try again later")
+    } else {
+        None
+    };
 
-        // Inject port if not specified (required by debug adapter)
-        if config_json.get("port").is_none() {
-            config_json["port"] = serde_json::json!(9005);
-        }
+    match cause {
+        Some(cause) => format!(
+            "Failed to download debug adapter from '{}': {} ({})",
+            url, cause, error
+        ),
+        None => format!("Failed to download debug adapter from '{}': {}", url, error),
+    }
+}
 
-        Ok(DebugAdapterBinary {
-            command: Some(ahk_exe),
-            arguments: vec![adapter_script],
-            envs: vec![],
-            cwd: Some(worktree.root_path()),
-            connection: None,
-            request_args: StartDebuggingRequestArguments {
-                configuration: config_json.to_string(),
-                request,
-            },
-        })
+fn parse_request_type(
+    field: &str,
+    value: &str,
+) -> Result<StartDebuggingRequestArgumentsRequest, String> {
+    match value {
+        "launch" => Ok(StartDebuggingRequestArgumentsRequest::Launch),
+        "attach" => Ok(StartDebuggingRequestArgumentsRequest::Attach),
+        other => Err(format!(
+            "Invalid {} type '{}', expected 'launch' or 'attach'",
+            field, other
+        )),
     }
+}
 
-    fn parse_request_kind(
-        config_json: &str,
-    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
-        let config: serde_json::Value = serde_json::from_str(config_json)
-            .map_err(|e| format!("Failed to parse config JSON: {}", e))?;
+/// Reads `request`, falling back to `defaultRequest` and then to launch when it's absent.
+/// The result must be listed in `allowedRequests`, which defaults to both kinds.
+fn request_type_from_config(
+    config: &serde_json::Value,
+) -> Result<StartDebuggingRequestArgumentsRequest, String> {
+    let (field, request) = match config.get("request").and_then(|v| v.as_str()) {
+        Some(request) => ("request", request),
+        None => match config.get("defaultRequest").and_then(|v| v.as_str()) {
+            Some(default) => ("defaultRequest", default),
+            None => ("request", "launch"),
+        },
+    };
+    let kind = parse_request_type(field, request)?;
 
-        request_type_from_config(&config)
+    let Some(allowed) = config.get("allowedRequests") else {
+        return Ok(kind);
+    };
+    let allowed = allowed
+        .as_array()
+        .and_then(|kinds| {
+            kinds
+                .iter()
+                .map(|kind| kind.as_str())
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or("'allowedRequests' must be an array of request kinds ('launch', 'attach')")?;
+    for allowed_kind in &allowed {
+        parse_request_type("allowedRequests", allowed_kind)?;
+    }
+    if !allowed.contains(&request) {
+        return Err(format!(
+            "Request kind '{}' is not permitted by policy. 'allowedRequests' allows: {}",
+            request,
+            allowed.join(", ")
+        ));
     }
+    Ok(kind)
 }
 
-impl zed::Extension for AutoHotkeyDebugger {
-    fn new() -> Self {
-        Self {
-            cached_version: OnceLock::new(),
+fn validate_adapter_name(name: &str, expected: &str) -> Result<(), String> {
+    validate_adapter_name_with(name, expected, AdapterNameMatching::Strict)
+}
+
+/// How strictly the adapter name Zed passes must match the canonical name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AdapterNameMatching {
+    /// Only the exact canonical name is accepted.
+    Strict,
+    /// A namespaced name such as `myext.autohotkey` is also accepted.
+    Tolerant,
+}
+
+impl AdapterNameMatching {
+    /// Reads `adapterNameMatching` leniently; invalid values are reported by `resolve_config`.
+    fn from_config(config: &serde_json::Value) -> Self {
+        match config.get("adapterNameMatching").and_then(|v| v.as_str()) {
+            Some("tolerant") => Self::Tolerant,
+            _ => Self::Strict,
         }
     }
+}
 
-    fn get_dap_binary(
-        &mut self,
-        adapter_name: String,
-        config: DebugTaskDefinition,
-        user_provided_debug_adapter_path: Option<String>,
-        worktree: &Worktree,
-    ) -> Result<DebugAdapterBinary, String> {
-        validate_adapter_name(&adapter_name)?;
+fn validate_adapter_name_with(
+    name: &str,
+    expected: &str,
+    matching: AdapterNameMatching,
+) -> Result<(), String> {
+    let unprefixed = match matching {
+        AdapterNameMatching::Strict => name,
+        AdapterNameMatching::Tolerant => name
+            .rsplit_once(['.', ':', '/'])
+            .filter(|(namespace, _)| !namespace.is_empty())
+            .map_or(name, |(_, rest)| rest),
+    };
+    if unprefixed != expected {
+        return Err(format!(
+            "Unsupported adapter '{}', expected '{}'",
+            name, expected
+        ));
+    }
+    Ok(())
+}
 
-        let version = self.ensure_adapter_installed()?;
-        self.build_binary(&version, config, user_provided_debug_adapter_path, worktree)
+/// Outcome of looking for a previously downloaded adapter version.
+#[derive(Debug, PartialEq, Eq)]
+enum CachedVersion {
+    /// The newest install whose layout is complete.
+    Complete(String),
+    /// Versioned dirs exist, but every one of them is incomplete.
+    Incomplete(Vec<String>),
+    Missing,
+}
+
+/// Rejects versions that could escape the adapter directory when used in a path.
+fn check_version_name(version: &str) -> Result<(), String> {
+    if version.is_empty() || version.contains(['/', '\\']) || version.contains("..") {
+        return Err(format!("Invalid adapter version '{}'", version));
     }
+    Ok(())
+}
 
-    fn dap_request_kind(
-        &mut self,
-        adapter_name: String,
-        config: serde_json::Value,
-    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
-        validate_adapter_name(&adapter_name)?;
+/// Reads `adapterVersion`, a release to install instead of the latest one. A leading
+/// `v` is accepted and dropped.
+fn pinned_version(config: &serde_json::Value) -> Result<Option<String>, String> {
+    let Some(value) = config.get("adapterVersion").filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    value
+        .as_str()
+        .map(|version| version.trim().trim_start_matches('v'))
+        .filter(|version| parse_version(version).is_some())
+        .map(|version| Some(version.to_owned()))
+        .ok_or_else(|| {
+            format!(
+                "Invalid 'adapterVersion' value {}, expected a release version such as \"1.2.0\"",
+                value
+            )
+        })
+}
 
-        request_type_from_config(&config)
+/// Reads `adapterRepository`, an `owner/name` GitHub repository to install the adapter
+/// from instead of [`GITHUB_REPO`].
+fn adapter_repository(config: &serde_json::Value) -> Result<Option<String>, String> {
+    let Some(value) = config.get("adapterRepository").filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && !part.starts_with('.')
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    value
+        .as_str()
+        .map(str::trim)
+        .filter(|repository| {
+            repository
+                .split_once('/')
+                .is_some_and(|(owner, name)| valid_part(owner) && valid_part(name))
+        })
+        .map(|repository| Some(repository.to_owned()))
+        .ok_or_else(|| {
+            format!(
+                "Invalid 'adapterRepository' value {}, expected a GitHub repository such as \"{}\"",
+                value, GITHUB_REPO
+            )
+        })
+}
+
+/// Parses a dotted numeric version such as `1.2.10` into comparable parts.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<_>>>()
+}
+
+/// Returns the newest complete `<adapter_name>_<version>` entry reported by `list_dir`.
+///
+/// A concurrent session may still be extracting its install, so the listing is
+/// re-read up to `attempts` times until a complete version shows up.
+fn find_cached_version(
+    adapter_name: &str,
+    mut list_dir: impl FnMut() -> Vec<String>,
+    is_complete: impl Fn(&str) -> bool,
+    attempts: u32,
+    retry_delay: Duration,
+) -> CachedVersion {
+    let prefix = format!("{}_", adapter_name);
+    let mut incomplete = Vec::new();
+
+    for attempt in 0..attempts.max(1) {
+        if attempt > 0 {
+            thread::sleep(retry_delay);
+        }
+
+        let (complete, rest): (Vec<String>, Vec<String>) = list_dir()
+            .iter()
+            .filter_map(|name| name.strip_prefix(&prefix).map(ToOwned::to_owned))
+            .partition(|version| is_complete(version));
+
+        if let Some(version) = complete.into_iter().max_by_key(|v| parse_version(v)) {
+            return CachedVersion::Complete(version);
+        }
+        incomplete = rest;
     }
 
-    fn dap_config_to_scenario(&mut self, config: DebugConfig) -> Result<DebugScenario, String> {
-        validate_adapter_name(&config.adapter)?;
+    if incomplete.is_empty() {
+        CachedVersion::Missing
+    } else {
+        incomplete.sort();
+        CachedVersion::Incomplete(incomplete)
+    }
+}
 
-        let scenario_config = match &config.request {
-            DebugRequest::Launch(launch) => {
-                // Validate program file exists
-                if !launch.program.is_empty() && !Path::new(&launch.program).exists() {
-                    return Err(format!(
-                        "Script file not found: '{}'. Check the 'program' path in your debug configuration.",
-                        launch.program
-                    ));
-                }
+/// Removes `dir` via `remove`, retrying briefly because antivirus scanners often hold
+/// freshly extracted executables open. A directory that is already gone is not an error.
+fn remove_dir_with_retry(
+    dir: &Path,
+    mut remove: impl FnMut(&Path) -> io::Result<()>,
+    attempts: u32,
+    retry_delay: Duration,
+) -> Result<(), String> {
+    let mut last_err = None;
 
-                serde_json::json!({
-                    "request": "launch",
-                    "program": launch.program,
-                    "cwd": launch.cwd,
-                    "args": launch.args,
-                    "stopOnEntry": config.stop_on_entry.unwrap_or(false),
-                    "port": 9005,
-                })
-            }
-            DebugRequest::Attach(_) => {
-                return Err("AutoHotkey debugger does not support attach mode".into());
-            }
-        };
+    for attempt in 0..attempts.max(1) {
+        if attempt > 0 {
+            thread::sleep(retry_delay);
+        }
 
-        Ok(DebugScenario {
-            adapter: config.adapter,
-            label: config.label,
-            build: None,
-            config: scenario_config.to_string(),
-            tcp_connection: None,
-        })
+        match remove(dir) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => last_err = Some(e),
+        }
     }
+
+    Err(format!(
+        "Failed to remove old adapter install at '{}': {}. The files may be locked by antivirus \
+         software or a running AutoHotkey process; close it and try again.",
+        dir.display(),
+        last_err.map(|e| e.to_string()).unwrap_or_default()
+    ))
 }
 
-zed::register_extension!(AutoHotkeyDebugger);
+/// Total size in bytes of the files under `dir`. Unreadable entries count as empty.
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .sum()
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use zed_extension_api::{AttachRequest, Extension, LaunchRequest};
+fn list_dir_names(dir: &str) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-    // ==================== request_type_from_config tests ====================
+/// Merges the config's `env` object over the inherited environment; config values win.
+fn merge_envs(inherited: EnvVars, config: &serde_json::Value) -> Result<EnvVars, String> {
+    let mut envs = inherited;
 
-    #[test]
-    fn request_type_from_config_returns_launch_for_launch_request() {
-        // Arrange
-        let config = serde_json::json!({"request": "launch"});
+    let Some(config_env) = config.get("env") else {
+        return Ok(envs);
+    };
+    let config_env = config_env
+        .as_object()
+        .ok_or("'env' must be an object of string values")?;
 
-        // Act
-        let result = request_type_from_config(&config);
+    for (name, value) in config_env {
+        let value = value
+            .as_str()
+            .ok_or_else(|| format!("Environment variable '{}' must be a string", name))?;
 
-        // Assert
-        assert!(matches!(
-            result,
-            Ok(StartDebuggingRequestArgumentsRequest::Launch)
-        ));
+        match envs.iter_mut().find(|(existing, _)| existing == name) {
+            Some(entry) => entry.1 = value.to_owned(),
+            None => envs.push((name.clone(), value.to_owned())),
+        }
     }
 
-    #[test]
-    fn request_type_from_config_returns_attach_for_attach_request() {
-        // Arrange
-        let config = serde_json::json!({"request": "attach"});
+    Ok(envs)
+}
 
-        // Act
-        let result = request_type_from_config(&config);
+/// Exposes `libPath` to the script as [`LIB_PATH_ENV`], expanding `${workspaceFolder}`.
+/// It overrides any inherited or `env` value of the same variable.
+fn apply_lib_path(
+    mut envs: EnvVars,
+    config: &serde_json::Value,
+    workspace_folder: &str,
+    home: &dyn Fn() -> Option<String>,
+) -> Result<EnvVars, String> {
+    let Some(lib_path) = config.get("libPath") else {
+        return Ok(envs);
+    };
+    let lib_path = lib_path
+        .as_str()
+        .ok_or("'libPath' must be a string")?
+        .replace(WORKSPACE_FOLDER_VAR, workspace_folder);
+    let lib_path = paths::expand_home(paths::trim_trailing(&lib_path), home)?;
 
-        // Assert
-        assert!(matches!(
-            result,
-            Ok(StartDebuggingRequestArgumentsRequest::Attach)
+    envs.retain(|(name, _)| name != LIB_PATH_ENV);
+    envs.push((LIB_PATH_ENV.to_owned(), lib_path));
+    Ok(envs)
+}
+
+/// Resolves `dataDir` against the workspace, expanding `${workspaceFolder}`. Absolute
+/// paths such as `%APPDATA%` locations are used as given; a relative path must stay
+/// inside the workspace, since the directory is created before launch.
+fn resolve_data_dir(
+    config: &serde_json::Value,
+    workspace_folder: &str,
+    home: &dyn Fn() -> Option<String>,
+) -> Result<Option<PathBuf>, String> {
+    let Some(data_dir) = config.get("dataDir") else {
+        return Ok(None);
+    };
+    let data_dir = data_dir
+        .as_str()
+        .ok_or("'dataDir' must be a string")?
+        .replace(WORKSPACE_FOLDER_VAR, workspace_folder);
+    let data_dir = paths::expand_home(paths::trim_trailing(&data_dir), home)?;
+    if Path::new(&data_dir).is_absolute() {
+        return Ok(Some(PathBuf::from(data_dir)));
+    }
+    paths::ensure_within(Path::new(workspace_folder), Path::new(&data_dir)).map(Some)
+}
+
+/// Exposes `dataDir` to the script as [`DATA_DIR_ENV`], overriding any inherited or
+/// `env` value of the same variable.
+fn apply_data_dir(mut envs: EnvVars, data_dir: Option<&Path>) -> EnvVars {
+    if let Some(data_dir) = data_dir {
+        envs.retain(|(name, _)| name != DATA_DIR_ENV);
+        envs.push((
+            DATA_DIR_ENV.to_owned(),
+            data_dir.to_string_lossy().into_owned(),
         ));
     }
+    envs
+}
 
-    #[test]
-    fn request_type_from_config_returns_error_for_invalid_request() {
-        // Arrange
-        let config = serde_json::json!({"request": "invalid"});
+/// Builds the script's environment from the shell environment, `env`, `libPath` and
+/// `dataDir`, returning the resolved data directory alongside. Nothing is written to disk.
+fn script_envs(
+    config: &serde_json::Value,
+    shell_env: EnvVars,
+    workspace_folder: &str,
+) -> Result<(EnvVars, Option<PathBuf>), String> {
+    let home = paths::home_dir(|name| env_var(&shell_env, name));
+    let envs = merge_envs(shell_env, config)?;
+    let envs = apply_lib_path(envs, config, workspace_folder, &|| home.clone())?;
+    let data_dir = resolve_data_dir(config, workspace_folder, &|| home.clone())?;
+    Ok((apply_data_dir(envs, data_dir.as_deref()), data_dir))
+}
+
+/// Creates the `dataDir` directory (and its parents) if it doesn't exist yet.
+fn create_data_dir(data_dir: Option<&Path>) -> Result<(), String> {
+    match data_dir {
+        Some(dir) => std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create data directory '{}': {}", dir.display(), e)),
+        None => Ok(()),
+    }
+}
+
+fn is_secret_env(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    SECRET_ENV_PATTERNS
+        .iter()
+        .any(|pattern| name.contains(pattern))
+}
+
+fn mask_secret_envs(envs: EnvVars) -> EnvVars {
+    envs.into_iter()
+        .map(|(name, value)| {
+            if is_secret_env(&name) {
+                (name, MASKED_ENV_VALUE.to_owned())
+            } else {
+                (name, value)
+            }
+        })
+        .collect()
+}
+
+/// Collects the [`DIAGNOSTIC_ENV_KEYS`] that are set, in allowlist order.
+fn diagnostic_env(env_lookup: impl Fn(&str) -> Option<String>) -> EnvVars {
+    let envs = DIAGNOSTIC_ENV_KEYS
+        .iter()
+        .filter_map(|name| env_lookup(name).map(|value| (name.to_string(), value)))
+        .collect();
+    mask_secret_envs(envs)
+}
+
+/// Validates a `compound` list of program paths, returning them when present.
+fn validate_compound(config: &serde_json::Value) -> Result<Option<Vec<String>>, String> {
+    let Some(compound) = config.get("compound") else {
+        return Ok(None);
+    };
+    let entries = compound
+        .as_array()
+        .filter(|entries| !entries.is_empty())
+        .ok_or("'compound' must be a non-empty array of program paths")?;
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let program = entry
+                .as_str()
+                .filter(|program| !program.is_empty())
+                .ok_or_else(|| format!("'compound[{}]' must be a program path", index))?;
+
+            if !Path::new(program).exists() {
+                return Err(format!(
+                    "Script file not found: '{}' (compound[{}]). Check the 'compound' list in your debug configuration.",
+                    program, index
+                ));
+            }
+            Ok(program.to_owned())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+/// Validates that an `exeName` override is a bare filename, not a path.
+fn validate_exe_name(exe_name: &str) -> Result<&str, String> {
+    let is_bare = !exe_name.is_empty()
+        && exe_name != "."
+        && exe_name != ".."
+        && !exe_name.contains(['/', '\\']);
+    if !is_bare {
+        return Err(format!(
+            "Invalid 'exeName' '{}': expected a file name such as 'AutoHotkey64.exe', without a directory",
+            exe_name
+        ));
+    }
+    Ok(exe_name)
+}
+
+/// Lists the paths where a system-wide AutoHotkey install may live, most common first.
+fn system_runtime_candidates(env_lookup: impl Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    SYSTEM_RUNTIME_DIRS
+        .iter()
+        .filter_map(|(var, fallback, subdir)| {
+            let root = env_lookup(var)
+                .filter(|root| !root.is_empty())
+                .or_else(|| fallback.map(ToOwned::to_owned))?;
+            Some(Path::new(&root).join(subdir))
+        })
+        .flat_map(|dir| SYSTEM_RUNTIME_EXES.iter().map(move |exe| dir.join(exe)))
+        .collect()
+}
+
+/// Returns the first candidate that exists.
+fn find_system_runtime(candidates: &[PathBuf], exists: impl Fn(&Path) -> bool) -> Option<String> {
+    candidates
+        .iter()
+        .find(|candidate| exists(candidate))
+        .map(|candidate| candidate.to_string_lossy().into_owned())
+}
+
+/// With `useSystemRuntime`, finds an installed AutoHotkey.exe in the standard locations.
+/// Extensions run sandboxed without registry access, so installs elsewhere need
+/// `runtimeExecutable`. `env_lookup` reads the install roots such as `ProgramFiles`.
+fn system_runtime_exe(
+    config: &serde_json::Value,
+    env_lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Option<String>, String> {
+    if !config::bool_field(config, "useSystemRuntime", false)? {
+        return Ok(None);
+    }
+    if zed::current_platform().0 != zed::Os::Windows {
+        return Err("'useSystemRuntime' is only supported on Windows".into());
+    }
+
+    let candidates = system_runtime_candidates(env_lookup);
+    match find_system_runtime(&candidates, Path::is_file) {
+        Some(exe) => Ok(Some(exe)),
+        None => Err(format!(
+            "No system AutoHotkey installation found. Searched: {}. Install AutoHotkey or set 'runtimeExecutable'.",
+            candidates
+                .iter()
+                .map(|candidate| candidate.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Looks up the executable `runtimeMap` assigns to the configured `runtimeVersion`.
+fn mapped_runtime_exe(config: &serde_json::Value) -> Result<Option<String>, String> {
+    let Some(runtime_map) = config.get("runtimeMap") else {
+        return Ok(None);
+    };
+    let runtime_map = runtime_map
+        .as_object()
+        .ok_or("'runtimeMap' must be an object mapping runtime versions to AutoHotkey.exe paths")?;
+    let runtime_version = config
+        .get("runtimeVersion")
+        .and_then(|v| v.as_str())
+        .unwrap_or(DEFAULT_RUNTIME_VERSION);
+
+    let Some(exe) = runtime_map.get(runtime_version) else {
+        return Ok(None);
+    };
+    let exe = exe
+        .as_str()
+        .ok_or_else(|| format!("'runtimeMap.{}' must be a path string", runtime_version))?;
+    if !Path::new(exe).is_file() {
+        return Err(format!(
+            "AutoHotkey runtime for '{}' not found at '{}'. Check 'runtimeMap' in your debug configuration.",
+            runtime_version, exe
+        ));
+    }
+    Ok(Some(exe.to_owned()))
+}
+
+/// The [`BUNDLED_RUNTIME_EXES`] that fit `runtime_version`: the 64- and 32-bit builds
+/// for `v2`, `AutoHotkey.exe` for `v1`, and all of them when no version is selected.
+fn bundled_runtime_exes(runtime_version: Option<&str>) -> &'static [&'static str] {
+    match runtime_version {
+        Some("v2") => &BUNDLED_RUNTIME_EXES[1..],
+        Some(_) => &BUNDLED_RUNTIME_EXES[..1],
+        None => &BUNDLED_RUNTIME_EXES,
+    }
+}
+
+/// Finds the bundled runtime in `bin_dir`: the first of the [`bundled_runtime_exes`] for
+/// `runtime_version` that is present. Every lookup of the bundled runtime goes through
+/// here, so install checks and launches agree on the exe.
+fn find_bundled_runtime(bin_dir: &Path, runtime_version: Option<&str>) -> Option<String> {
+    bundled_runtime_exes(runtime_version)
+        .iter()
+        .map(|name| bin_dir.join(name))
+        .find(|exe| exe.is_file())
+        .map(|exe| exe.to_string_lossy().into_owned())
+}
+
+/// Picks the AutoHotkey.exe that runs the adapter script. An explicit runtime wins over
+/// a `runtimeMap` entry, then a discovered system install (`useSystemRuntime`), then the
+/// bundled one; with `bundledRuntime: false` the bundled exe is never considered.
+/// Unless `exeName` or `exeSubpath` names one, the bundled runtime is detected among
+/// the [`bundled_runtime_exes`] for `runtimeVersion`.
+fn resolve_runtime_exe(
+    config: &serde_json::Value,
+    user_provided_path: Option<String>,
+    paths: &AdapterPaths,
+    env_lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String, BuildError> {
+    let bundled_runtime = config::bool_field(config, "bundledRuntime", true)?;
+    let skip_validation = config::bool_field(config, "skipBinaryValidation", false)?;
+    let runtime_executable = config
+        .get("runtimeExecutable")
+        .and_then(|v| v.as_str())
+        .filter(|path| !path.is_empty())
+        .map(ToOwned::to_owned);
+    let exe_name = config
+        .get("exeName")
+        .map(|v| v.as_str().ok_or("'exeName' must be a string"))
+        .transpose()?
+        .map(validate_exe_name)
+        .transpose()?;
+
+    match runtime_executable.or(user_provided_path) {
+        Some(exe) if Path::new(&exe).exists() => return Ok(exe),
+        Some(exe) => {
+            return Err(BuildError::Invalid(format!(
+                "AutoHotkey runtime not found at '{}'. Check 'runtimeExecutable' in your debug configuration.",
+                exe
+            )))
+        }
+        None => {}
+    }
+    if let Some(exe) = mapped_runtime_exe(config)? {
+        return Ok(exe);
+    }
+    if let Some(exe) = system_runtime_exe(config, env_lookup)? {
+        return Ok(exe);
+    }
+
+    if !bundled_runtime {
+        return Err(
+            "'bundledRuntime' is false but no AutoHotkey runtime was provided. Set 'runtimeExecutable' to the path of AutoHotkey.exe."
+                .into(),
+        );
+    }
+    match exe_name {
+        Some(exe_name) => {
+            let exe = Path::new(&paths.exe).with_file_name(exe_name);
+            if !skip_validation && !exe.is_file() {
+                return Err(BuildError::Invalid(format!(
+                    "Debug adapter '{}' not found at '{}'. Check 'exeName' in your debug configuration.",
+                    exe_name,
+                    exe.display()
+                )));
+            }
+            Ok(exe.to_string_lossy().into_owned())
+        }
+        None if !skip_validation && config.get("exeSubpath").is_none() => {
+            let runtime_version = config.get("runtimeVersion").and_then(|v| v.as_str());
+            let names = bundled_runtime_exes(runtime_version);
+            let bin_dir = Path::new(&paths.exe).parent().unwrap_or(Path::new(""));
+            if let Some(exe) = find_bundled_runtime(bin_dir, runtime_version) {
+                return Ok(exe);
+            }
+            if !paths.versioned_dir_exists || !paths.script_exists {
+                return Err(BuildError::MissingFiles(
+                    paths.missing_component_message().unwrap_or_else(|| {
+                        format!("Debug adapter AutoHotkey.exe not found at '{}'", paths.exe)
+                    }),
+                ));
+            }
+            let runtime = match runtime_version {
+                Some(version) => format!("AutoHotkey {} runtime", version),
+                None => "AutoHotkey runtime".to_owned(),
+            };
+            // Other runtimes being present means the install is whole, just not for
+            // the selected `runtimeVersion`
+            let ships_no_runtime = find_bundled_runtime(bin_dir, None).is_none();
+            let message = format!(
+                "No {} found in '{}'. Looked for {}. Set 'exeName' to the runtime the adapter ships, or delete '{}' to force a fresh download.",
+                runtime,
+                bin_dir.display(),
+                names.join(", "),
+                paths.versioned_dir
+            );
+            if ships_no_runtime {
+                Err(BuildError::MissingFiles(message))
+            } else {
+                Err(BuildError::Invalid(message))
+            }
+        }
+        // Validate bundled AHK runtime exists
+        None if !paths.exe_exists => Err(BuildError::MissingFiles(
+            paths.missing_component_message().unwrap_or_else(|| {
+                format!("Debug adapter AutoHotkey.exe not found at '{}'", paths.exe)
+            }),
+        )),
+        None => Ok(paths.exe.clone()),
+    }
+}
+
+/// Builds the arguments passed to the AutoHotkey runtime. With `noDebug` the program is
+/// run directly, so neither the adapter script nor a debug port is involved.
+/// Runtime switches (`/ErrorStdOut` for `outputCapture: "std"`, `/CP<n>` for `codePage`)
+/// must precede the script path.
+fn runtime_arguments(config: &serde_json::Value, adapter_script: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    if config.get("outputCapture").and_then(|v| v.as_str()) == Some("std") {
+        arguments.push(ERROR_STDOUT_FLAG.to_owned());
+    }
+    if let Some(code_page) = config.get("codePage").and_then(|v| v.as_u64()) {
+        arguments.push(format!("/CP{}", code_page));
+    }
+
+    if config.get("noDebug") != Some(&serde_json::Value::Bool(true)) {
+        arguments.push(adapter_script.to_owned());
+        return arguments;
+    }
+
+    let program = config
+        .get("program")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let args = config
+        .get("args")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|arg| arg.as_str());
+
+    arguments.push(program.to_owned());
+    arguments.extend(args.map(ToOwned::to_owned));
+    arguments
+}
+
+/// Reads the port from the environment variable named by `portEnv`, so CI can assign
+/// ports at launch. It takes precedence over a static `port`.
+fn port_from_env(config: &serde_json::Value, envs: &EnvVars) -> Result<Option<u16>, String> {
+    let Some(name) = config.get("portEnv") else {
+        return Ok(None);
+    };
+    let name = name
+        .as_str()
+        .filter(|name| !name.trim().is_empty())
+        .ok_or("'portEnv' must be the name of an environment variable")?;
+
+    let value = envs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+        .ok_or_else(|| format!("'portEnv' names '{}', which is not set", name))?;
+    value
+        .trim()
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port > 0)
+        .map(Some)
+        .ok_or_else(|| {
+            format!(
+                "Invalid '{}' value '{}' from 'portEnv', expected a port number",
+                name, value
+            )
+        })
+}
+
+/// With `transport: "tcp"`, tells Zed to connect to the adapter on localhost at the
+/// configured port. Stdio, the default, needs no connection details.
+fn adapter_connection(config: &serde_json::Value) -> Result<Option<TcpArguments>, String> {
+    let is_tcp = config.get("transport").and_then(|v| v.as_str()) == Some("tcp");
+    if !is_tcp || config::bool_field(config, "noDebug", false)? {
+        return Ok(None);
+    }
+
+    let port = config
+        .get("port")
+        .and_then(|v| v.as_u64())
+        .and_then(|port| u16::try_from(port).ok())
+        .filter(|port| *port > 0)
+        .ok_or_else(|| {
+            format!(
+                "Invalid 'port' value {}, expected a port number for the tcp transport",
+                config.get("port").unwrap_or(&serde_json::Value::Null)
+            )
+        })?;
+    let timeout = Limits::from_config(config)?.connect_timeout;
+
+    Ok(Some(TcpArguments {
+        port,
+        host: u32::from(std::net::Ipv4Addr::LOCALHOST),
+        timeout: Some(timeout.as_millis() as u64),
+    }))
+}
+
+/// Like [`adapter_connection`], but also connects over TCP when the saved scenario carries
+/// a `tcp_connection`. The scenario's port is only a default: a `port` in the
+/// configuration, which is always set once resolved, overrides it.
+fn scenario_connection(
+    config: &serde_json::Value,
+    tcp_connection: Option<&TcpArgumentsTemplate>,
+) -> Result<Option<TcpArguments>, String> {
+    if let Some(connection) = adapter_connection(config)? {
+        return Ok(Some(connection));
+    }
+    let Some(tcp) = tcp_connection else {
+        return Ok(None);
+    };
+    if config::bool_field(config, "noDebug", false)? {
+        return Ok(None);
+    }
+
+    let port = config
+        .get("port")
+        .and_then(|v| v.as_u64())
+        .and_then(|port| u16::try_from(port).ok())
+        .filter(|port| *port > 0)
+        .or(tcp.port)
+        .unwrap_or(DEFAULT_PORT);
+    let timeout = tcp
+        .timeout
+        .unwrap_or(Limits::from_config(config)?.connect_timeout.as_millis() as u64);
+
+    Ok(Some(TcpArguments {
+        port,
+        host: tcp.host.unwrap_or(u32::from(std::net::Ipv4Addr::LOCALHOST)),
+        timeout: Some(timeout),
+    }))
+}
+
+/// Reads `program` and checks its `#Requires` directive against `runtime_version`.
+/// Unreadable or missing files are left to the existence checks.
+fn check_program_runtime_version(program: &str, runtime_version: &str) -> Result<(), String> {
+    match std::fs::read(program) {
+        Ok(bytes) => script::check_runtime_version(
+            program,
+            &String::from_utf8_lossy(&bytes),
+            runtime_version,
+        ),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Label for scenarios saved without one, so they don't show up blank in the UI.
+fn default_label(request: &DebugRequest) -> String {
+    match request {
+        DebugRequest::Launch(launch) => {
+            match Path::new(paths::trim_trailing(&launch.program)).file_name() {
+                Some(name) => format!("AutoHotkey: {}", name.to_string_lossy()),
+                None => "AutoHotkey".to_string(),
+            }
+        }
+        DebugRequest::Attach(_) => "AutoHotkey Attach".to_string(),
+    }
+}
+
+/// The home directory from the Zed process environment, for `dap_config_to_scenario`,
+/// which has no worktree shell environment to read it from.
+fn user_home() -> Option<String> {
+    paths::home_dir(|name| env::var(name).ok())
+}
+
+/// Resolves a `${fileDirname}` cwd to the directory containing `program`.
+fn resolve_cwd(cwd: Option<&str>, program: &str) -> Result<Option<String>, String> {
+    match cwd {
+        Some(FILE_DIRNAME_VAR) => Path::new(program)
+            .parent()
+            .filter(|dir| !program.is_empty() && !dir.as_os_str().is_empty())
+            .map(|dir| Some(dir.to_string_lossy().into_owned()))
+            .ok_or_else(|| {
+                format!(
+                    "Cannot resolve cwd '{}': 'program' has no parent directory",
+                    FILE_DIRNAME_VAR
+                )
+            }),
+        other => Ok(other.map(ToOwned::to_owned)),
+    }
+}
+
+/// Picks the cwd when the config doesn't set one: the worktree root, then the directory
+/// containing `program`, then the process working directory.
+fn default_cwd(
+    worktree_root: &str,
+    program: &str,
+    process_cwd: impl FnOnce() -> Option<String>,
+) -> Result<String, String> {
+    if !worktree_root.trim().is_empty() {
+        return Ok(worktree_root.to_owned());
+    }
+    let program_dir = Path::new(program)
+        .parent()
+        .filter(|dir| !program.is_empty() && !dir.as_os_str().is_empty());
+    if let Some(dir) = program_dir {
+        return Ok(dir.to_string_lossy().into_owned());
+    }
+    process_cwd().filter(|cwd| !cwd.is_empty()).ok_or_else(|| {
+        "Cannot determine a working directory: the worktree root is empty and 'program' has no directory. Set 'cwd' in your debug configuration.".to_string()
+    })
+}
+
+/// Resolves a relative path such as `cwd` against the worktree root; absolute paths
+/// are kept.
+fn resolve_in_worktree(path: &str, worktree_root: &str) -> PathBuf {
+    Path::new(worktree_root).join(path)
+}
+
+/// Checks that the working directory exists, unless `validateCwd` is false because the
+/// directory is only created once the script runs.
+fn validate_cwd(config: &serde_json::Value, cwd: &str, worktree_root: &str) -> Result<(), String> {
+    if !config::bool_field(config, "validateCwd", true)?
+        || resolve_in_worktree(cwd, worktree_root).is_dir()
+    {
+        return Ok(());
+    }
+    Err(format!(
+        "Working directory not found: '{}'. Check 'cwd' in your debug configuration, or set \"validateCwd\": false if it is created later.",
+        cwd
+    ))
+}
+
+/// With `createCwd`, creates a missing working directory (and its parents) before
+/// launch and returns it resolved against the worktree, so a relative `cwd` names the
+/// directory that was created. Only directories inside the worktree are created.
+/// Without `createCwd`, `cwd` is returned unchanged.
+fn create_cwd(
+    config: &serde_json::Value,
+    cwd: &str,
+    worktree_root: &str,
+) -> Result<String, String> {
+    if !config::bool_field(config, "createCwd", false)? {
+        return Ok(cwd.to_owned());
+    }
+    let existing = resolve_in_worktree(cwd, worktree_root);
+    if existing.is_dir() {
+        return Ok(existing.to_string_lossy().into_owned());
+    }
+    let dir = paths::ensure_within(Path::new(worktree_root), Path::new(cwd))?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create working directory '{}': {}", cwd, e))?;
+    Ok(dir.to_string_lossy().into_owned())
+}
+
+/// Returns a copy of `config` without the [`EXTENSION_ONLY_KEYS`], for the adapter.
+fn adapter_configuration(config: &serde_json::Value) -> serde_json::Value {
+    let mut config = config.clone();
+    if let Some(object) = config.as_object_mut() {
+        for key in EXTENSION_ONLY_KEYS {
+            object.remove(key);
+        }
+    }
+    config
+}
+
+/// Returns a copy of `config` with secret `env` values and all `requestHeaders` values
+/// masked, safe to show or log.
+fn redact_config(config: &serde_json::Value) -> serde_json::Value {
+    let mut redacted = config.clone();
+    if let Some(env) = redacted.get_mut("env").and_then(|v| v.as_object_mut()) {
+        for (name, value) in env.iter_mut() {
+            if is_secret_env(name) {
+                *value = serde_json::json!(MASKED_ENV_VALUE);
+            }
+        }
+    }
+    if let Some(headers) = redacted
+        .get_mut("requestHeaders")
+        .and_then(|v| v.as_object_mut())
+    {
+        headers
+            .values_mut()
+            .for_each(|value| *value = serde_json::json!(MASKED_ENV_VALUE));
+    }
+    redacted
+}
+
+static SESSION_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a fresh `session_<time>_<n>` directory under `root` so concurrent sessions
+/// never share temp files. Session directories older than `max_age` are removed first,
+/// on a best-effort basis.
+fn create_session_dir(root: &Path, max_age: Duration) -> Result<PathBuf, String> {
+    cleanup_stale_session_dirs(root, max_age);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let counter = SESSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let dir = paths::ensure_within(
+        root,
+        Path::new(&format!("{}{}_{}", SESSION_DIR_PREFIX, nanos, counter)),
+    )?;
+
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create temp session directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Removes session directories under `root` last modified at least `max_age` ago.
+/// Directories still in use by a running session are recent, so they are kept.
+fn cleanup_stale_session_dirs(root: &Path, max_age: Duration) {
+    let now = SystemTime::now();
+    for entry in std::fs::read_dir(root).into_iter().flatten().flatten() {
+        let is_session = entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(SESSION_DIR_PREFIX);
+        let is_stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= max_age);
+        if is_session && is_stale {
+            std::fs::remove_dir_all(entry.path()).ok();
+        }
+    }
+}
+
+/// Writes inline `scriptContent` to a `.ahk` file in a new session directory under
+/// `dir` and points `program` at it. Temp scripts from earlier sessions are removed once
+/// they are older than `tempScriptMaxAgeMs` (default [`STALE_SESSION_AGE`]).
+fn write_script_content(config: &mut serde_json::Value, dir: &Path) -> Result<(), String> {
+    let Some(content) = config.get("scriptContent") else {
+        return Ok(());
+    };
+    let content = content
+        .as_str()
+        .ok_or("'scriptContent' must be a string")?
+        .to_owned();
+
+    let has_program = config
+        .get("program")
+        .and_then(|v| v.as_str())
+        .is_some_and(|program| !program.is_empty());
+    if has_program {
+        return Err(
+            "'scriptContent' and 'program' are mutually exclusive; set only one of them".into(),
+        );
+    }
+
+    let max_age = config::positive_int_field(config, "tempScriptMaxAgeMs")?
+        .map_or(STALE_SESSION_AGE, Duration::from_millis);
+    let script_path = create_session_dir(dir, max_age)?.join(TEMP_SCRIPT_NAME);
+
+    std::fs::write(&script_path, content)
+        .map_err(|e| format!("Failed to write temp script: {}", e))?;
+
+    let object = config
+        .as_object_mut()
+        .ok_or("Config must be a JSON object")?;
+    object.remove("scriptContent");
+    object.insert(
+        "program".into(),
+        serde_json::json!(script_path.to_string_lossy()),
+    );
+    Ok(())
+}
+
+/// Replaces `argsFile` with the arguments it lists, one per line. Blank lines are
+/// skipped and home references are expanded. The file path expands `${workspaceFolder}`
+/// and, when relative, is resolved against the workspace. Conflicts with a non-empty
+/// `args`.
+fn apply_args_file(
+    config: &mut serde_json::Value,
+    workspace_folder: &str,
+    home: &dyn Fn() -> Option<String>,
+) -> Result<(), String> {
+    let Some(args_file) = config.get("argsFile") else {
+        return Ok(());
+    };
+    let args_file = args_file
+        .as_str()
+        .ok_or("'argsFile' must be a string")?
+        .replace(WORKSPACE_FOLDER_VAR, workspace_folder);
+    let args_file = paths::expand_home(&args_file, home)?;
+    let args_file = resolve_in_worktree(&args_file, workspace_folder);
+
+    let has_args = config
+        .get("args")
+        .and_then(|v| v.as_array())
+        .is_some_and(|args| !args.is_empty());
+    if has_args {
+        return Err("'args' and 'argsFile' are mutually exclusive; set only one of them".into());
+    }
+
+    let content = std::fs::read_to_string(&args_file)
+        .map_err(|e| format!("Failed to read 'argsFile' '{}': {}", args_file.display(), e))?;
+    let args = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| paths::expand_home(line, home))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let object = config
+        .as_object_mut()
+        .ok_or("Config must be a JSON object")?;
+    object.remove("argsFile");
+    object.insert("args".into(), serde_json::json!(args));
+    Ok(())
+}
+
+/// Validates `readinessProbe` and fills in `intervalMs`/`maxAttempts` when it's given
+/// without them, so the adapter and Zed agree on how long to retry connecting.
+fn resolve_readiness_probe(config: &mut serde_json::Value) -> Result<(), String> {
+    let probe = match config.get_mut("readinessProbe") {
+        None | Some(serde_json::Value::Null) => return Ok(()),
+        Some(serde_json::Value::Object(probe)) => probe,
+        Some(other) => {
+            return Err(format!(
+                "Invalid 'readinessProbe' value {}, expected an object with 'intervalMs' and 'maxAttempts'",
+                other
+            ))
+        }
+    };
+
+    for (field, default) in [
+        ("intervalMs", DEFAULT_PROBE_INTERVAL_MS),
+        ("maxAttempts", DEFAULT_PROBE_MAX_ATTEMPTS),
+    ] {
+        let value = match probe.get(field) {
+            None | Some(serde_json::Value::Null) => default,
+            Some(value) => value.as_u64().filter(|n| *n > 0).ok_or_else(|| {
+                format!(
+                    "Invalid 'readinessProbe.{}' value {}, expected a positive integer",
+                    field, value
+                )
+            })?,
+        };
+        probe.insert(field.into(), serde_json::json!(value));
+    }
+    Ok(())
+}
+
+/// Validates `stopAtLine`/`stopAtFile`, the initial breakpoint set on launch. The file
+/// defaults to `program` so a line alone stops in the main script.
+fn resolve_stop_at(
+    config: &mut serde_json::Value,
+    home: &dyn Fn() -> Option<String>,
+) -> Result<(), String> {
+    let line = config::positive_int_field(config, "stopAtLine")?;
+    let file = config
+        .get("stopAtFile")
+        .map(|v| v.as_str().ok_or("'stopAtFile' must be a string"))
+        .transpose()?
+        .map(paths::trim_trailing)
+        .map(|file| paths::expand_home(file, home))
+        .transpose()?;
+
+    let Some(_) = line else {
+        if file.is_some() {
+            return Err("'stopAtFile' requires 'stopAtLine'".into());
+        }
+        return Ok(());
+    };
+
+    match file {
+        Some(file) if !Path::new(&file).is_file() => Err(format!(
+            "'stopAtFile' not found: '{}'. Check the path in your debug configuration.",
+            file
+        )),
+        Some(file) => {
+            config["stopAtFile"] = serde_json::json!(file);
+            Ok(())
+        }
+        None => {
+            if let Some(program) = config.get("program").cloned() {
+                config["stopAtFile"] = program;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Reads a runtime build such as `1.1.33.02` from the runtime path, e.g. a portable
+/// `AutoHotkey_1.1.33.02/AutoHotkey.exe`. Returns `None` when the path names no version.
+fn runtime_build_from_path(exe: &str) -> Option<Vec<u64>> {
+    Path::new(exe)
+        .parent()?
+        .components()
+        .rev()
+        .find_map(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            let start = name.find(|c: char| c.is_ascii_digit())?;
+            let version = name[start..]
+                .split(|c: char| !c.is_ascii_digit() && c != '.')
+                .next()?
+                .trim_end_matches('.');
+            version
+                .contains('.')
+                .then(|| parse_version(version))
+                .flatten()
+        })
+}
+
+/// What to do about `stopOnEntry` on a runtime older than [`STOP_ON_ENTRY_MIN_RUNTIME`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StopOnEntryFallback {
+    /// `stopOnEntry` or `stopOnEntryFallback` is off, or the runtime is new enough or
+    /// unknown.
+    NotNeeded,
+    /// Stop on line 1 of `program` through `stopAtLine` instead.
+    EntryBreakpoint,
+}
+
+/// Decides how to handle `stopOnEntry` for `runtime_build`. With `stopOnEntryFallback`
+/// an entry breakpoint is injected; otherwise (the default) the config is left alone.
+fn stop_on_entry_fallback(
+    config: &serde_json::Value,
+    runtime_build: Option<&[u64]>,
+) -> Result<StopOnEntryFallback, String> {
+    let use_breakpoint = config::bool_field(config, "stopOnEntryFallback", false)?;
+    let stop_on_entry = config::bool_field(config, "stopOnEntry", false)?;
+    match runtime_build {
+        Some(build)
+            if use_breakpoint && stop_on_entry && build < STOP_ON_ENTRY_MIN_RUNTIME.as_slice() =>
+        {
+            Ok(StopOnEntryFallback::EntryBreakpoint)
+        }
+        _ => Ok(StopOnEntryFallback::NotNeeded),
+    }
+}
+
+/// Applies [`stop_on_entry_fallback`]. An existing `stopAtLine` is kept, since the
+/// session already stops somewhere the user chose. Runtimes inside `install_dir` are
+/// bundled with the adapter, whose path carries the adapter version rather than the
+/// AutoHotkey build, so they are never treated as old.
+fn apply_stop_on_entry_fallback(
+    config: &mut serde_json::Value,
+    runtime_exe: &str,
+    install_dir: &str,
+) -> Result<(), String> {
+    let runtime_build = if Path::new(runtime_exe).starts_with(install_dir) {
+        None
+    } else {
+        runtime_build_from_path(runtime_exe)
+    };
+    match stop_on_entry_fallback(config, runtime_build.as_deref())? {
+        StopOnEntryFallback::NotNeeded => {}
+        StopOnEntryFallback::EntryBreakpoint => {
+            if config.get("stopAtLine").is_none() {
+                if let Some(program) = config.get("program").cloned() {
+                    config["stopAtLine"] = serde_json::json!(1);
+                    config["stopAtFile"] = program;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sets `terminalTitle`, the window title of a console the adapter opens for the
+/// script, defaulting to the session label so the window is easy to recognize.
+fn resolve_terminal_title(config: &mut serde_json::Value, label: &str) -> Result<(), String> {
+    match config.get("terminalTitle") {
+        None | Some(serde_json::Value::Null) => {
+            config["terminalTitle"] = serde_json::json!(label);
+            Ok(())
+        }
+        Some(serde_json::Value::String(_)) => Ok(()),
+        Some(other) => Err(format!(
+            "Invalid 'terminalTitle' value {}, expected a string",
+            other
+        )),
+    }
+}
+
+/// Validates `hitConditions`, hit-count breakpoints the adapter sets at start. Each entry
+/// needs a `line` and a `condition` such as `">= 3"`; `file` defaults to `program`.
+fn resolve_hit_conditions(config: &mut serde_json::Value) -> Result<(), String> {
+    let entries = match config.get("hitConditions") {
+        None | Some(serde_json::Value::Null) => return Ok(()),
+        Some(serde_json::Value::Array(entries)) => entries.clone(),
+        Some(other) => {
+            return Err(format!(
+                "Invalid 'hitConditions' value {}, expected an array of {{\"line\", \"condition\"}} objects",
+                other
+            ))
+        }
+    };
+    let program = config.get("program").cloned();
+
+    let mut resolved = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let line = config::positive_int_field(entry, "line")
+            .map_err(|e| format!("'hitConditions[{}]': {}", i, e))?;
+        let condition = entry
+            .get("condition")
+            .and_then(|v| v.as_str())
+            .filter(|condition| !condition.trim().is_empty());
+        let (Some(line), Some(condition)) = (line, condition) else {
+            return Err(format!(
+                "Invalid 'hitConditions[{}]' value {}, expected a positive 'line' and a non-empty 'condition'",
+                i, entry
+            ));
+        };
+        let file = match entry.get("file") {
+            Some(file) => Some(
+                file.as_str()
+                    .map(serde_json::Value::from)
+                    .ok_or_else(|| format!("'hitConditions[{}].file' must be a string", i))?,
+            ),
+            None => program.clone(),
+        };
+
+        let mut hit_condition = serde_json::json!({"line": line, "condition": condition});
+        if let Some(file) = file {
+            hit_condition["file"] = file;
+        }
+        resolved.push(hit_condition);
+    }
+    config["hitConditions"] = serde_json::Value::Array(resolved);
+    Ok(())
+}
+
+/// Checks that `locale` looks like a BCP 47 tag such as `de`, `pt-BR`, or `zh-Hant-TW`:
+/// a 2-3 letter language followed by 1-8 character alphanumeric subtags.
+fn validate_locale(config: &serde_json::Value) -> Result<(), String> {
+    let Some(locale) = config.get("locale") else {
+        return Ok(());
+    };
+    let is_tag = locale.as_str().is_some_and(|locale| {
+        let mut subtags = locale.split(['-', '_']);
+        let language = subtags.next().unwrap_or_default();
+        (2..=3).contains(&language.len())
+            && language.chars().all(|c| c.is_ascii_alphabetic())
+            && subtags.all(|subtag| {
+                (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+    });
+    if is_tag {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid 'locale' value {}, expected a language tag such as \"en\" or \"pt-BR\"",
+            locale
+        ))
+    }
+}
+
+/// Validates `pathMappings`, the `{localRoot, remoteRoot}` pairs the adapter uses to
+/// translate script paths when debugging AutoHotkey on another machine.
+fn validate_path_mappings(config: &serde_json::Value) -> Result<(), String> {
+    let mappings = match config.get("pathMappings") {
+        None | Some(serde_json::Value::Null) => return Ok(()),
+        Some(serde_json::Value::Array(mappings)) => mappings,
+        Some(other) => {
+            return Err(format!(
+                "Invalid 'pathMappings' value {}, expected an array of {{\"localRoot\", \"remoteRoot\"}} objects",
+                other
+            ))
+        }
+    };
+
+    for (i, mapping) in mappings.iter().enumerate() {
+        for root in ["localRoot", "remoteRoot"] {
+            let valid = mapping
+                .get(root)
+                .and_then(|v| v.as_str())
+                .is_some_and(|path| !path.trim().is_empty());
+            if !valid {
+                return Err(format!(
+                    "Invalid 'pathMappings[{}]' value {}, expected a non-empty '{}'",
+                    i, mapping, root
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Why the adapter binary couldn't be built. Only missing install files are worth an
+/// automatic repair; anything else, such as a typo in the debug configuration, is
+/// reported as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BuildError {
+    /// A file the adapter install should contain is missing.
+    MissingFiles(String),
+    Invalid(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingFiles(message) | Self::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for BuildError {
+    fn from(message: String) -> Self {
+        Self::Invalid(message)
+    }
+}
+
+impl From<&str> for BuildError {
+    fn from(message: &str) -> Self {
+        Self::Invalid(message.to_owned())
+    }
+}
+
+impl From<BuildError> for String {
+    fn from(err: BuildError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Resolved install paths for one adapter version, with existence checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AdapterPaths {
+    versioned_dir: String,
+    exe: String,
+    script: String,
+    versioned_dir_exists: bool,
+    exe_exists: bool,
+    script_exists: bool,
+}
+
+impl AdapterPaths {
+    fn new(versioned_dir: String, exe: String, script: String) -> Self {
+        Self {
+            versioned_dir_exists: Path::new(&versioned_dir).is_dir(),
+            exe_exists: Path::new(&exe).is_file(),
+            script_exists: Path::new(&script).is_file(),
+            versioned_dir,
+            exe,
+            script,
+        }
+    }
+
+    /// Like [`Self::new`], but assumes every path exists instead of checking, for
+    /// `skipBinaryValidation`.
+    fn assumed(versioned_dir: String, exe: String, script: String) -> Self {
+        Self {
+            versioned_dir_exists: true,
+            exe_exists: true,
+            script_exists: true,
+            versioned_dir,
+            exe,
+            script,
+        }
+    }
+
+    /// An install is complete once the sentinel has been written. Installs made before
+    /// the sentinel existed, or whose directory is read-only, count as complete when
+    /// every expected file is present.
+    fn is_complete(&self) -> bool {
+        Path::new(&self.versioned_dir)
+            .join(INSTALL_SENTINEL)
+            .is_file()
+            || (self.exe_exists && self.script_exists)
+    }
+
+    /// Explains which part of the install is missing and how to recover, or `None` when
+    /// both the runtime and the adapter script are present.
+    fn missing_component_message(&self) -> Option<String> {
+        let force_reinstall = format!(
+            "Delete '{}' to force a fresh download on the next debug session.",
+            self.versioned_dir
+        );
+        match (self.versioned_dir_exists, self.exe_exists, self.script_exists) {
+            (_, true, true) => None,
+            (false, _, _) => Some(format!(
+                "Debug adapter is not installed at '{}'. Try reinstalling the extension.",
+                self.versioned_dir
+            )),
+            (true, false, true) => Some(format!(
+                "Debug adapter AutoHotkey.exe not found at '{}', but the adapter script is present, so the downloaded archive was incomplete. {}",
+                self.exe, force_reinstall
+            )),
+            (true, true, false) => Some(format!(
+                "Debug adapter script not found at '{}', but AutoHotkey.exe is present, so the downloaded archive was incomplete. {}",
+                self.script, force_reinstall
+            )),
+            (true, false, false) => Some(format!(
+                "Debug adapter install at '{}' is empty or corrupt. {}",
+                self.versioned_dir, force_reinstall
+            )),
+        }
+    }
+}
+
+/// Snapshot of the adapter install, as reported by [`AutoHotkeyDebugger::install_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallStatus {
+    /// Newest complete install on disk.
+    pub installed_version: Option<String>,
+    /// Latest release on GitHub, or `None` when it couldn't be looked up.
+    pub latest_version: Option<String>,
+    pub network_reachable: bool,
+}
+
+impl InstallStatus {
+    pub fn is_installed(&self) -> bool {
+        self.installed_version.is_some()
+    }
+
+    pub fn update_available(&self) -> bool {
+        match (&self.installed_version, &self.latest_version) {
+            (Some(installed), Some(latest)) => installed != latest,
+            _ => false,
+        }
+    }
+}
+
+/// Looks up the latest adapter release of a repository (`owner/name`).
+pub type ReleaseFetcher = Box<dyn Fn(&str) -> Result<GithubRelease, String> + Send + Sync>;
+
+/// Looks up the adapter release of a repository (first argument) published under a tag
+/// such as `v1.2.0` (second).
+pub type TaggedReleaseFetcher =
+    Box<dyn Fn(&str, &str) -> Result<GithubRelease, String> + Send + Sync>;
+
+/// Downloads and extracts the asset at a URL (first argument) into a directory (second).
+pub type Downloader = Box<dyn Fn(&str, &str) -> Result<(), String> + Send + Sync>;
+
+/// Phases of an adapter download, reported to a [`ProgressReporter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadEvent {
+    Started { version: String, url: String },
+    Completed { version: String },
+    Failed { version: String, error: String },
+}
+
+/// Receives [`DownloadEvent`]s. `download_file` exposes no byte counts, so only the
+/// phases are reported.
+pub type ProgressReporter = Box<dyn Fn(&DownloadEvent) + Send + Sync>;
+
+pub struct AutoHotkeyDebugger {
+    adapter_name: String,
+    cached_version: OnceLock<String>,
+    resolved_config: Option<String>,
+    install_scope: Option<String>,
+    /// Repository (`owner/name`) the adapter is installed from.
+    repository: String,
+    base_dir: Option<PathBuf>,
+    fetch_release: ReleaseFetcher,
+    fetch_tagged_release: TaggedReleaseFetcher,
+    download: Downloader,
+    report_progress: Option<ProgressReporter>,
+}
+
+fn latest_release(repository: &str) -> Result<GithubRelease, String> {
+    latest_github_release(
+        repository,
+        GithubReleaseOptions {
+            require_assets: true,
+            pre_release: false,
+        },
+    )
+}
+
+fn release_by_tag(repository: &str, tag: &str) -> Result<GithubRelease, String> {
+    github_release_by_tag_name(repository, tag)
+}
+
+fn download_zip(url: &str, dest: &str) -> Result<(), String> {
+    download_file(url, dest, DownloadedFileType::Zip)
+}
+
+impl AutoHotkeyDebugger {
+    /// Creates a debugger that keeps its installs under `base_dir` instead of the
+    /// extension's working directory. Combine with [`Self::with_release_fetcher`] and
+    /// [`Self::with_downloader`] to exercise the install flow without network access.
+    pub fn with_base_dir(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: Some(base_dir.into()),
+            ..zed::Extension::new()
+        }
+    }
+
+    /// Sets the canonical adapter name, for forks that register a variant such as
+    /// `autohotkey-h`. It is the name Zed must pass and names the install directories.
+    /// Defaults to [`ADAPTER_NAME`].
+    pub fn with_adapter_name(mut self, adapter_name: impl Into<String>) -> Self {
+        self.adapter_name = adapter_name.into();
+        self
+    }
+
+    pub fn with_release_fetcher(
+        mut self,
+        fetch_release: impl Fn(&str) -> Result<GithubRelease, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.fetch_release = Box::new(fetch_release);
+        self
+    }
+
+    pub fn with_tagged_release_fetcher(
+        mut self,
+        fetch_tagged_release: impl Fn(&str, &str) -> Result<GithubRelease, String>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.fetch_tagged_release = Box::new(fetch_tagged_release);
+        self
+    }
+
+    pub fn with_downloader(
+        mut self,
+        download: impl Fn(&str, &str) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.download = Box::new(download);
+        self
+    }
+
+    /// Reports when an adapter download starts, completes, or fails.
+    pub fn with_progress_reporter(
+        mut self,
+        report_progress: impl Fn(&DownloadEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.report_progress = Some(Box::new(report_progress));
+        self
+    }
+
+    fn report(&self, event: DownloadEvent) {
+        if let Some(report_progress) = &self.report_progress {
+            report_progress(&event);
+        }
+    }
+
+    fn base_dir(&self) -> PathBuf {
+        self.base_dir
+            .clone()
+            .unwrap_or_else(|| env::current_dir().unwrap())
+    }
+
+    fn adapter_dir(&self) -> String {
+        let base = self.base_dir();
+        // Scoped installs live outside the shared adapter dir, which is wiped on upgrade
+        let base = match &self.install_scope {
+            Some(scope) => base.join(WORKTREE_SCOPES_DIR).join(scope),
+            None => base,
+        };
+        // Another repository may publish the same version numbers, so keep its installs apart
+        let base = if self.repository == GITHUB_REPO {
+            base
+        } else {
+            base.join(REPOSITORY_SCOPES_DIR)
+                .join(worktree_scope(&self.repository))
+        };
+        base.join(&self.adapter_name).to_string_lossy().into_owned()
+    }
+
+    fn temp_scripts_dir(&self) -> String {
+        self.base_dir()
+            .join(TEMP_SCRIPTS_DIR)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn versioned_dir(&self, version: &str) -> String {
+        format!("{}/{}_{}", self.adapter_dir(), self.adapter_name, version)
+    }
+
+    /// Looks up the latest release and picks its adapter asset: the one named
+    /// `asset_name` when given, otherwise the first `.vsix`.
+    fn fetch_latest_release(
+        &self,
+        asset_name: Option<&str>,
+    ) -> Result<(GithubReleaseAsset, String), String> {
+        let release = (self.fetch_release)(&self.repository)?;
+
+        let version = release.version.trim_start_matches('v').to_string();
+        let expected_name = format!("autohotkey-debug-{}.vsix", version);
+
+        let asset = match asset_name {
+            Some(asset_name) => {
+                let available = release
+                    .assets
+                    .iter()
+                    .map(|a| a.name.clone())
+                    .collect::<Vec<_>>();
+                release
+                    .assets
+                    .into_iter()
+                    .find(|a| a.name == asset_name)
+                    .ok_or_else(|| {
+                        format!(
+                            "Release {} has no asset named '{}' (set by 'assetName'). Available: {}",
+                            version,
+                            asset_name,
+                            available.join(", ")
+                        )
+                    })?
+            }
+            None => release
+                .assets
+                .into_iter()
+                .find(|a| a.name.ends_with(".vsix"))
+                .ok_or_else(|| {
+                    format!(
+                        "No .vsix asset found in release (expected {})",
+                        expected_name
+                    )
+                })?,
+        };
+
+        if asset.name.len() <= ".vsix".len() || asset.name.contains(['/', '\\']) {
+            return Err(format!(
+                "Release {} has a malformed adapter asset name '{}' (expected {})",
+                version, asset.name, expected_name
+            ));
+        }
+        if asset.download_url.trim().is_empty() {
+            return Err(format!(
+                "Release asset '{}' for version {} has no download URL",
+                asset.name, version
+            ));
+        }
+
+        Ok((asset, version))
+    }
+
+    /// Resolves the download URL of the asset published for `version`: the one named
+    /// `asset_name` when given, otherwise `autohotkey-debug-<version>.vsix` or, failing
+    /// that, the first `.vsix`.
+    pub fn download_url_for(
+        &self,
+        version: &str,
+        asset_name: Option<&str>,
+    ) -> Result<String, String> {
+        let version = version.trim_start_matches('v');
+        check_version_name(version)?;
+
+        let release = (self.fetch_tagged_release)(&self.repository, &format!("v{}", version))
+            .map_err(|e| format!("Adapter version {} not found: {}", version, e))?;
+
+        let expected_name = asset_name
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| format!("autohotkey-debug-{}.vsix", version));
+        let asset = release
+            .assets
+            .iter()
+            .find(|a| a.name == expected_name)
+            .or_else(|| match asset_name {
+                Some(_) => None,
+                None => release.assets.iter().find(|a| a.name.ends_with(".vsix")),
+            })
+            .filter(|a| !a.download_url.trim().is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "Release {} has no downloadable .vsix asset (expected {})",
+                    version, expected_name
+                )
+            })?;
+        Ok(asset.download_url.clone())
+    }
+
+    /// Reports what is installed and whether a newer release exists, without downloading.
+    /// Does a single release lookup; when offline only the cached installs are reported.
+    pub fn install_status(&self) -> InstallStatus {
+        let latest_version = (self.fetch_release)(&self.repository)
+            .ok()
+            .map(|release| release.version.trim_start_matches('v').to_string());
+
+        let installed_version = match &latest_version {
+            Some(latest) if self.adapter_paths(latest).is_complete() => Some(latest.clone()),
+            _ => {
+                let adapter_dir = self.adapter_dir();
+                match find_cached_version(
+                    &self.adapter_name,
+                    || list_dir_names(&adapter_dir),
+                    |version| self.adapter_paths(version).is_complete(),
+                    1,
+                    Duration::ZERO,
+                ) {
+                    CachedVersion::Complete(version) => Some(version),
+                    CachedVersion::Incomplete(_) | CachedVersion::Missing => None,
+                }
+            }
+        };
+
+        InstallStatus {
+            installed_version,
+            network_reachable: latest_version.is_some(),
+            latest_version,
+        }
+    }
+
+    /// Deletes one installed adapter version, keeping the others. Does nothing if that
+    /// version isn't installed.
+    pub fn remove_version(&mut self, version: &str) -> Result<(), String> {
+        check_version_name(version)?;
+
+        remove_dir_with_retry(
+            Path::new(&self.versioned_dir(version)),
+            |dir| std::fs::remove_dir_all(dir),
+            REMOVE_DIR_ATTEMPTS,
+            REMOVE_DIR_RETRY_DELAY,
+        )?;
+
+        if self.cached_version.get().map(String::as_str) == Some(version) {
+            self.cached_version.take();
+        }
+        Ok(())
+    }
+
+    /// Re-downloads `version` if its runtime or adapter script is missing, leaving other
+    /// installed versions alone. Returns whether a repair was needed.
+    pub fn repair_install(&mut self, version: &str) -> Result<bool, String> {
+        check_version_name(version)?;
+        let paths = self.adapter_paths(version);
+        if paths.exe_exists && paths.script_exists {
+            return Ok(false);
+        }
+        self.reinstall(version, &InstallOptions::default())?;
+        Ok(true)
+    }
+
+    /// Installs the `adapterVersion` release unless it is installed already. A pinned
+    /// version is never swapped for another one, so there is no cache fallback.
+    fn ensure_pinned_version_installed(
+        &self,
+        version: &str,
+        options: &InstallOptions,
+    ) -> Result<String, String> {
+        if !self.adapter_paths(version).is_complete() {
+            let url = self.download_url_for(version, options.asset_name.as_deref())?;
+            self.install_asset(&url, version, options, |err| err)?;
+        }
+        Ok(version.to_owned())
+    }
+
+    /// Replaces the `version` install with a fresh download of its release asset.
+    fn reinstall(&mut self, version: &str, options: &InstallOptions) -> Result<(), String> {
+        check_version_name(version)?;
+        let url = self.download_url_for(version, options.asset_name.as_deref())?;
+        self.install_asset(&url, version, options, |err| err)?;
+
+        if let Some(message) = self.adapter_paths(version).missing_component_message() {
+            return Err(format!("Repair of adapter {} failed: {}", version, message));
+        }
+        Ok(())
+    }
+
+    /// Downloads `url` as the `version` install. The archive is extracted next to the
+    /// install and checked first, so a failed or rejected download leaves any existing
+    /// install in place; only a download that passes replaces it. `annotate` adds
+    /// context to download errors.
+    fn install_asset(
+        &self,
+        url: &str,
+        version: &str,
+        options: &InstallOptions,
+        annotate: impl Fn(String) -> String,
+    ) -> Result<(), String> {
+        let versioned_dir = self.versioned_dir(version);
+        // `download_file` always verifies certificates and takes no TLS options
+        if options.allow_insecure_downloads {
+            return Err(format!(
+                "'allowInsecureDownloads' is set, but Zed downloads the adapter itself and cannot skip TLS certificate checks. \
+                 Trust your mirror's certificate in the operating system, or extract '{}' to '{}' by hand",
+                url, versioned_dir
+            ));
+        }
+        std::fs::create_dir_all(self.adapter_dir())
+            .map_err(|e| format!("Failed to create adapter directory: {}", e))?;
+        // Not a `<name>_<version>` entry, so never mistaken for an install
+        let staging_dir = format!("{}{}", versioned_dir, STAGING_SUFFIX);
+        let remove = |dir: &str| {
+            remove_dir_with_retry(
+                Path::new(dir),
+                |dir| std::fs::remove_dir_all(dir),
+                REMOVE_DIR_ATTEMPTS,
+                REMOVE_DIR_RETRY_DELAY,
+            )
+        };
+        remove(&staging_dir)?;
+
+        self.report(DownloadEvent::Started {
+            version: version.to_owned(),
+            url: url.to_owned(),
+        });
+        let fail = |error: String| {
+            self.report(DownloadEvent::Failed {
+                version: version.to_owned(),
+                error: error.clone(),
+            });
+            error
+        };
+
+        if let Err(err) = (self.download)(url, &staging_dir) {
+            remove(&staging_dir).ok();
+            return Err(fail(annotate(diagnose_download_error(url, &err))));
+        }
+
+        // A zero-length or truncated archive can extract to nothing, or to only part of
+        // the adapter, without an error, which would otherwise only surface as missing
+        // files later
+        let size = dir_size(Path::new(&staging_dir));
+        let script =
+            paths::ensure_within(Path::new(&staging_dir), Path::new(&options.script_subpath))
+                .unwrap_or_else(|_| Path::new(&staging_dir).join(ADAPTER_SCRIPT_SUBPATH));
+        let rejection = if size == 0 {
+            Some(format!(
+                "Downloaded debug adapter from '{}' extracted no files; the download was empty or truncated (is the disk full?)",
+                url
+            ))
+        } else if size > options.limits.max_download_size {
+            Some(format!(
+                "Downloaded debug adapter is {} bytes, over the maxDownloadSizeBytes limit of {} bytes",
+                size, options.limits.max_download_size
+            ))
+        } else if !script.is_file() {
+            Some(format!(
+                "Downloaded debug adapter from '{}' has no adapter script at '{}'; the archive is incomplete or uses a different layout (set 'scriptSubpath')",
+                url,
+                Path::new(&versioned_dir)
+                    .join(&options.script_subpath)
+                    .display()
+            ))
+        } else {
+            None
+        };
+        if let Some(rejection) = rejection {
+            remove(&staging_dir).map_err(fail)?;
+            return Err(fail(rejection));
+        }
+        // Best effort: the layout check covers installs where this can't be written
+        std::fs::write(Path::new(&staging_dir).join(INSTALL_SENTINEL), version).ok();
+
+        remove(&versioned_dir).map_err(fail)?;
+        std::fs::rename(&staging_dir, &versioned_dir).map_err(|e| {
+            fail(format!(
+                "Failed to move the downloaded debug adapter into '{}': {}",
+                versioned_dir, e
+            ))
+        })?;
+        self.report(DownloadEvent::Completed {
+            version: version.to_owned(),
+        });
+        Ok(())
+    }
+
+    /// Runs `build`, and if it fails because files of the `version` install went missing
+    /// after [`Self::ensure_adapter_installed`] checked them (a concurrent cleanup or a
+    /// corrupted install), reinstalls and retries once. The build resolves the runtime
+    /// and script itself, so its [`BuildError::MissingFiles`] is the integrity check;
+    /// any other error is returned without touching the install.
+    fn build_with_repair<T>(
+        &mut self,
+        version: &str,
+        options: &InstallOptions,
+        build: impl Fn(&Self) -> Result<T, BuildError>,
+    ) -> Result<T, BuildError> {
+        let mut repaired = false;
+        loop {
+            match build(self) {
+                Err(BuildError::MissingFiles(err)) if !repaired => {
+                    repaired = true;
+                    if let Err(repair_err) = self.reinstall(version, options) {
+                        return Err(BuildError::MissingFiles(format!(
+                            "{} (automatic repair failed: {})",
+                            err, repair_err
+                        )));
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Removes installed versions beyond the newest `keep`, never removing `current`.
+    fn cleanup_old_versions(&mut self, current: &str, keep: usize) -> Result<(), String> {
+        let stale = self
+            .cached_versions()
+            .into_iter()
+            .filter(|version| version != current)
+            .skip(keep.saturating_sub(1))
+            .collect::<Vec<_>>();
+        for version in stale {
+            self.remove_version(&version)?;
+        }
+        Ok(())
+    }
+
+    /// Lists the adapter versions installed locally, newest first. Entries whose name
+    /// isn't `<adapter name>_<version>` with a numeric version are skipped.
+    pub fn cached_versions(&self) -> Vec<String> {
+        let prefix = format!("{}_", self.adapter_name);
+        let mut versions: Vec<(Vec<u64>, String)> = list_dir_names(&self.adapter_dir())
+            .into_iter()
+            .filter_map(|name| name.strip_prefix(&prefix).map(ToOwned::to_owned))
+            .filter_map(|version| parse_version(&version).map(|parsed| (parsed, version)))
+            .collect();
+        versions.sort_by(|a, b| b.0.cmp(&a.0));
+        versions.into_iter().map(|(_, version)| version).collect()
+    }
+
+    fn ensure_adapter_installed(&mut self, options: &InstallOptions) -> Result<String, String> {
+        if let Some(version) = &options.version {
+            return self.ensure_pinned_version_installed(version, options);
+        }
+        if let Some(version) = self.cached_version.get() {
+            // The cached version may not be installed in the current worktree scope yet
+            if options.skip_validation || self.adapter_paths(version).is_complete() {
+                return Ok(version.clone());
+            }
+        }
+
+        if options.cache_first {
+            let adapter_dir = self.adapter_dir();
+            if let CachedVersion::Complete(version) = find_cached_version(
+                &self.adapter_name,
+                || list_dir_names(&adapter_dir),
+                |version| self.adapter_paths(version).is_complete(),
+                1,
+                Duration::ZERO,
+            ) {
+                self.cached_version.set(version.clone()).ok();
+                return Ok(version);
+            }
+        }
+
+        let started = std::time::Instant::now();
+        let slow_network = |err: String| {
+            let elapsed = started.elapsed();
+            if elapsed <= options.limits.network_timeout {
+                return err;
+            }
+            format!(
+                "{} (took {}ms, longer than networkTimeoutMs of {}ms; the network may be slow or blocked)",
+                err,
+                elapsed.as_millis(),
+                options.limits.network_timeout.as_millis()
+            )
+        };
+
+        match self
+            .fetch_latest_release(options.asset_name.as_deref())
+            .map_err(slow_network)
+        {
+            Ok((asset, version)) => {
+                // The version comes from the release tag, so keep it from escaping the adapter dir
+                let versioned_dir = self.versioned_dir(&version);
+                paths::ensure_within(Path::new(&self.adapter_dir()), Path::new(&versioned_dir))?;
+
+                if !self.adapter_paths(&version).is_complete() {
+                    self.install_asset(&asset.download_url, &version, options, slow_network)?;
+                    self.cached_version.set(version.clone()).ok();
+                    // Best effort: a locked old version must not fail a usable install, and
+                    // is removed on a later update
+                    self.cleanup_old_versions(&version, options.max_cached_versions)
+                        .ok();
+                }
+
+                self.cached_version.set(version.clone()).ok();
+                Ok(version)
+            }
+            Err(fetch_err) => {
+                let adapter_dir = self.adapter_dir();
+                let cached = find_cached_version(
+                    &self.adapter_name,
+                    || list_dir_names(&adapter_dir),
+                    |version| self.adapter_paths(version).is_complete(),
+                    options.cache_discovery_attempts,
+                    CACHE_DISCOVERY_RETRY_DELAY,
+                );
+
+                let mut message = match cached {
+                    CachedVersion::Complete(v) => {
+                        self.cached_version.set(v.clone()).ok();
+                        return Ok(v);
+                    }
+                    CachedVersion::Incomplete(versions) => format!(
+                        "Failed to fetch release ({}) and every cached install in '{}' is incomplete ({}). \
+                         Reconnect to reinstall the debug adapter, or delete that folder",
+                        fetch_err,
+                        adapter_dir,
+                        versions.join(", ")
+                    ),
+                    CachedVersion::Missing => format!(
+                        "Failed to fetch release and no cached version found: {}",
+                        fetch_err
+                    ),
+                };
+                message.push_str(&format!(
+                    " [{}]",
+                    user_agent(options.user_agent_suffix.as_deref())
+                ));
+                if let Some(note) = unsent_headers_note(&options.request_header_names) {
+                    message.push_str(". ");
+                    message.push_str(&note);
+                }
+                if let Some(proxy) = &options.proxy {
+                    message.push_str(". ");
+                    message.push_str(&proxy_guidance(proxy));
+                }
+                Err(message)
+            }
+        }
+    }
+
+    /// Path of the bundled runtime for `runtime_version`, as found by [`find_bundled_runtime`],
+    /// or the default `AutoHotkey.exe` path when none is present.
+    fn ahk_exe_path(&self, version: &str, runtime_version: Option<&str>) -> String {
+        let exe = Path::new(&self.versioned_dir(version)).join(AHK_EXE_SUBPATH);
+        exe.parent()
+            .and_then(|bin_dir| find_bundled_runtime(bin_dir, runtime_version))
+            .unwrap_or_else(|| exe.to_string_lossy().into_owned())
+    }
+
+    fn adapter_script_path(&self, version: &str) -> String {
+        Path::new(&self.versioned_dir(version))
+            .join(ADAPTER_SCRIPT_SUBPATH)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn adapter_paths(&self, version: &str) -> AdapterPaths {
+        AdapterPaths::new(
+            self.versioned_dir(version),
+            self.ahk_exe_path(version, None),
+            self.adapter_script_path(version),
+        )
+    }
+
+    /// Like [`Self::adapter_paths`], but honors `exeSubpath` and `scriptSubpath` for
+    /// `.vsix` archives with a non-standard layout. Overridden paths must exist, unless
+    /// `skipBinaryValidation` is set, in which case the paths are computed without
+    /// touching the file system.
+    fn configured_adapter_paths(
+        &self,
+        version: &str,
+        config: &serde_json::Value,
+    ) -> Result<AdapterPaths, BuildError> {
+        let validate = !config::bool_field(config, "skipBinaryValidation", false)?;
+        let versioned_dir = self.versioned_dir(version);
+        let resolve = |field: &str, default: &str| -> Result<String, BuildError> {
+            let Some(value) = config.get(field) else {
+                return Ok(Path::new(&versioned_dir)
+                    .join(default)
+                    .to_string_lossy()
+                    .into_owned());
+            };
+            let subpath = value
+                .as_str()
+                .filter(|subpath| !subpath.trim().is_empty())
+                .ok_or_else(|| format!("'{}' must be a non-empty relative path", field))?;
+            if Path::new(subpath).is_absolute() {
+                return Err(BuildError::Invalid(format!(
+                    "'{}' must be relative to the adapter install, got '{}'",
+                    field, subpath
+                )));
+            }
+            let path = paths::ensure_within(Path::new(&versioned_dir), Path::new(subpath))?;
+            // A configured subpath that doesn't exist is most likely a typo, which a
+            // fresh download wouldn't fix
+            if validate && !path.is_file() {
+                return Err(BuildError::Invalid(format!(
+                    "'{}' resolved to '{}', which does not exist",
+                    field,
+                    path.display()
+                )));
+            }
+            Ok(path.to_string_lossy().into_owned())
+        };
+
+        let runtime_version = config.get("runtimeVersion").and_then(|v| v.as_str());
+        let exe = match config.get("exeSubpath") {
+            Some(_) => resolve("exeSubpath", AHK_EXE_SUBPATH)?,
+            None if validate => self.ahk_exe_path(version, runtime_version),
+            // Without detection, assume the first runtime the version would pick
+            None => Path::new(&versioned_dir)
+                .join(AHK_EXE_SUBPATH)
+                .with_file_name(bundled_runtime_exes(runtime_version)[0])
+                .to_string_lossy()
+                .into_owned(),
+        };
+        let script = resolve("scriptSubpath", ADAPTER_SCRIPT_SUBPATH)?;
+        if validate {
+            Ok(AdapterPaths::new(versioned_dir, exe, script))
+        } else {
+            Ok(AdapterPaths::assumed(versioned_dir, exe, script))
+        }
+    }
+
+    fn build_binary(
+        &self,
+        version: &str,
+        config: DebugTaskDefinition,
+        user_provided_path: Option<String>,
+        worktree: &Worktree,
+    ) -> Result<DebugAdapterBinary, BuildError> {
+        let request = Self::parse_request_kind(&config.config)?;
+        let shell_env = worktree.shell_env();
+        let mut config_json =
+            self.resolve_config(&config.config, |name| env_var(&shell_env, name))?;
+        let home = paths::home_dir(|name| env_var(&shell_env, name));
+        apply_args_file(&mut config_json, &worktree.root_path(), &|| home.clone())?;
+
+        let paths = self.configured_adapter_paths(version, &config_json)?;
+        let ahk_exe = resolve_runtime_exe(&config_json, user_provided_path, &paths, |name| {
+            env_var(&shell_env, name)
+        })?;
+        apply_stop_on_entry_fallback(&mut config_json, &ahk_exe, &paths.versioned_dir)?;
+        resolve_terminal_title(&mut config_json, &config.label)?;
+
+        // Validate adapter script exists
+        let no_debug = config::bool_field(&config_json, "noDebug", false)?;
+        if !no_debug && !paths.script_exists {
+            return Err(BuildError::MissingFiles(
+                paths.missing_component_message().unwrap_or_else(|| {
+                    format!("Debug adapter script not found at '{}'", paths.script)
+                }),
+            ));
+        }
+        let arguments = runtime_arguments(&config_json, &paths.script);
+        let (envs, data_dir) = script_envs(&config_json, shell_env, &worktree.root_path())?;
+        create_data_dir(data_dir.as_deref())?;
+        if !no_debug {
+            if let Some(port) = port_from_env(&config_json, &envs)? {
+                config_json["port"] = serde_json::json!(port);
+            }
+        }
+        let cwd = match config_json.get("cwd").and_then(|v| v.as_str()) {
+            Some(cwd) => cwd.to_owned(),
+            None => default_cwd(
+                &worktree.root_path(),
+                config_json
+                    .get("program")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default(),
+                || {
+                    env::current_dir()
+                        .ok()
+                        .map(|dir| dir.to_string_lossy().into_owned())
+                },
+            )?,
+        };
+        let cwd = create_cwd(&config_json, &cwd, &worktree.root_path())?;
+        validate_cwd(&config_json, &cwd, &worktree.root_path())?;
+        let cwd = resolve_in_worktree(&cwd, &worktree.root_path())
+            .to_string_lossy()
+            .into_owned();
+
+        Ok(DebugAdapterBinary {
+            command: Some(ahk_exe),
+            arguments,
+            envs,
+            cwd: Some(cwd),
+            connection: scenario_connection(&config_json, config.tcp_connection.as_ref())?,
+            request_args: StartDebuggingRequestArguments {
+                configuration: adapter_configuration(&config_json).to_string(),
+                request,
+            },
+        })
+    }
+
+    /// Parses the debug configuration and injects the fields the adapter requires.
+    /// `env_lookup` reads the environment overrides, normally from the worktree's shell.
+    fn resolve_config(
+        &self,
+        config: &str,
+        env_lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<serde_json::Value, String> {
+        let mut config_json =
+            config::parse(config).map_err(|e| format!("Failed to parse config: {}", e))?;
+
+        paths::trim_trailing_in_config(&mut config_json);
+        paths::trim_separators_in_config(&mut config_json);
+
+        if let Some(programs) = validate_compound(&config_json)? {
+            return Err(format!(
+                "Compound debug sessions are not supported yet. Start a separate session for each of: {}",
+                programs.join(", ")
+            ));
+        }
+
+        config::apply_env_overrides(&mut config_json, &env_lookup)?;
+        let home = || paths::home_dir(&env_lookup);
+        // Headers only concern the install and may hold tokens, so keep them from the adapter
+        request_headers(&config_json, |_| None)?;
+        if let Some(obj) = config_json.as_object_mut() {
+            obj.remove("requestHeaders");
+        }
+        let no_debug = config::inject_bool_field(&mut config_json, "noDebug", false)?;
+
+        let merge_strategy = config::MergeStrategy::from_config(&config_json)?;
+
+        // Inject port (required by debug adapter, unused without it)
+        if no_debug {
+            if let Some(obj) = config_json.as_object_mut() {
+                obj.remove("port");
+            }
+        } else {
+            config::inject_default(
+                &mut config_json,
+                "port",
+                serde_json::json!(DEFAULT_PORT),
+                merge_strategy,
+            );
+        }
+
+        config::enum_field(&mut config_json, "trace", &TRACE_LEVELS, Some("off"))?;
+        config::enum_field(&mut config_json, "transport", &TRANSPORTS, Some("stdio"))?;
+        config::enum_field(
+            &mut config_json,
+            "adapterNameMatching",
+            &["strict", "tolerant"],
+            None,
+        )?;
+        config::enum_field(&mut config_json, "encoding", &SCRIPT_ENCODINGS, None)?;
+        config::enum_field(
+            &mut config_json,
+            "resolutionOrder",
+            &["network-first", "cache-first"],
+            Some("network-first"),
+        )?;
+        config::enum_field(
+            &mut config_json,
+            "exceptionBreakMode",
+            &EXCEPTION_BREAK_MODES,
+            Some("unhandled"),
+        )?;
+        config::enum_field(
+            &mut config_json,
+            "outputCapture",
+            &OUTPUT_CAPTURE_MODES,
+            Some("console"),
+        )?;
+        config::positive_int_field(&config_json, "keepAliveMs")?;
+        config::positive_int_field(&config_json, "variableDepth")?;
+        config::positive_int_field(&config_json, "maxCachedVersions")?;
+        config::positive_int_field(&config_json, "scriptTimeoutMs")?;
+        config::non_negative_int_field(&config_json, "codePage")?;
+        config::bool_object_field(&config_json, "capabilities")?;
+        config::non_empty_strings_field(&config_json, "watches")?;
+        validate_path_mappings(&config_json)?;
+        validate_locale(&config_json)?;
+        resolve_readiness_probe(&mut config_json)?;
+
+        let limits = Limits::from_config(&config_json)?;
+        config_json["connectTimeoutMs"] = serde_json::json!(limits.connect_timeout.as_millis());
+        config::inject_bool_field(&mut config_json, "restart", false)?;
+        config::inject_bool_field(&mut config_json, "followChildProcesses", false)?;
+        config::bool_field(&config_json, "allowInsecureDownloads", false)?;
+        pinned_version(&config_json)?;
+        adapter_repository(&config_json)?;
+
+        // Kill the script we launched on disconnect, but leave attached processes running
+        let is_attach = matches!(
+            request_type_from_config(&config_json),
+            Ok(StartDebuggingRequestArgumentsRequest::Attach)
+        );
+        config::inject_bool_field(&mut config_json, "terminateOnExit", !is_attach)?;
+
+        write_script_content(&mut config_json, Path::new(&self.temp_scripts_dir()))?;
+        paths::expand_home_in_config(&mut config_json, &home)?;
+        paths::resolve_program_in_search_paths(&mut config_json, &home)?;
+
+        let program = config_json
+            .get("program")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        let cwd = config_json.get("cwd").and_then(|v| v.as_str());
+        if let Some(cwd) = resolve_cwd(cwd, program)? {
+            config_json["cwd"] = serde_json::json!(cwd);
+        }
+        resolve_stop_at(&mut config_json, &home)?;
+        resolve_hit_conditions(&mut config_json)?;
+
+        // Only a runtime the user actually selected is compared with `#Requires`
+        config::enum_field(&mut config_json, "runtimeVersion", &RUNTIME_VERSIONS, None)?;
+        if let (Some(runtime_version), Some(program)) = (
+            config_json.get("runtimeVersion").and_then(|v| v.as_str()),
+            config_json.get("program").and_then(|v| v.as_str()),
+        ) {
+            check_program_runtime_version(program, runtime_version)?;
+        }
+
+        Ok(config_json)
+    }
+
+    /// Returns the configuration most recently sent to the adapter, with secrets masked.
+    pub fn resolved_config(&self) -> Option<&str> {
+        self.resolved_config.as_deref()
+    }
+
+    fn record_resolved_config(&mut self, configuration: &str) {
+        self.resolved_config = serde_json::from_str(configuration)
+            .ok()
+            .and_then(|config| serde_json::to_string_pretty(&redact_config(&config)).ok());
+    }
+
+    /// Converts `config` into the scenario Zed would run and returns it as a
+    /// `.zed/debug.json` entry. Keys are sorted so the output is stable to diff and share.
+    pub fn export_scenario(&mut self, config: DebugConfig) -> Result<String, String> {
+        let scenario = zed::Extension::dap_config_to_scenario(self, config)?;
+
+        let mut entry: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&scenario.config)
+                .map_err(|e| format!("Failed to parse scenario config: {}", e))?;
+        entry.insert("label".into(), serde_json::json!(scenario.label));
+        entry.insert("adapter".into(), serde_json::json!(scenario.adapter));
+        if let Some(tcp) = scenario.tcp_connection {
+            entry.insert(
+                "tcp_connection".into(),
+                serde_json::json!({
+                    "host": tcp.host,
+                    "port": tcp.port,
+                    "timeout": tcp.timeout,
+                }),
+            );
+        }
+
+        serde_json::to_string_pretty(&entry)
+            .map_err(|e| format!("Failed to serialize scenario: {}", e))
+    }
+
+    /// Returns the environment the debugged script will see, with secret values masked.
+    /// Only the environment is computed: no script is written and `dataDir` isn't created.
+    pub fn forwarded_envs(&self, config: &str, worktree: &Worktree) -> Result<EnvVars, String> {
+        let config_json =
+            config::parse(config).map_err(|e| format!("Failed to parse config: {}", e))?;
+        let (envs, _) = script_envs(&config_json, worktree.shell_env(), &worktree.root_path())?;
+        Ok(mask_secret_envs(envs))
+    }
+
+    /// Returns the command Zed would run to start the adapter for `config`, quoted for
+    /// the current platform's shell, so the adapter can be started by hand when
+    /// troubleshooting. Installs the adapter first if needed.
+    pub fn launch_command(
+        &mut self,
+        config: DebugTaskDefinition,
+        worktree: &Worktree,
+    ) -> Result<String, String> {
+        let binary =
+            zed::Extension::get_dap_binary(self, config.adapter.clone(), config, None, worktree)?;
+        let command = binary
+            .command
+            .ok_or("The debug adapter has no command to run")?;
+        let windows = zed::current_platform().0 == zed::Os::Windows;
+        Ok(shell_command(&command, &binary.arguments, windows))
+    }
+
+    /// Returns the allowlisted variables (`PATH`, `TEMP`, ...) of the worktree's shell
+    /// environment for inclusion in diagnostics. Variables outside the allowlist are never
+    /// reported.
+    pub fn environment_snapshot(&self, worktree: &Worktree) -> EnvVars {
+        let shell_env = worktree.shell_env();
+        diagnostic_env(|name| env_var(&shell_env, name))
+    }
+
+    fn parse_request_kind(
+        config_json: &str,
+    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
+        let config = config::parse(config_json)
+            .map_err(|e| format!("Failed to parse config JSON: {}", e))?;
+
+        request_type_from_config(&config)
+    }
+}
+
+impl zed::Extension for AutoHotkeyDebugger {
+    fn new() -> Self {
+        Self {
+            adapter_name: ADAPTER_NAME.to_owned(),
+            cached_version: OnceLock::new(),
+            resolved_config: None,
+            install_scope: None,
+            repository: GITHUB_REPO.to_owned(),
+            base_dir: None,
+            fetch_release: Box::new(latest_release),
+            fetch_tagged_release: Box::new(release_by_tag),
+            download: Box::new(download_zip),
+            report_progress: None,
+        }
+    }
+
+    fn get_dap_binary(
+        &mut self,
+        adapter_name: String,
+        config: DebugTaskDefinition,
+        user_provided_debug_adapter_path: Option<String>,
+        worktree: &Worktree,
+    ) -> Result<DebugAdapterBinary, String> {
+        let config_json = config::parse(&config.config).ok();
+        let matching = config_json.as_ref().map_or(
+            AdapterNameMatching::Strict,
+            AdapterNameMatching::from_config,
+        );
+        validate_adapter_name_with(&adapter_name, &self.adapter_name, matching)?;
+
+        let shell_env = worktree.shell_env();
+        let install_options = config_json
+            .map(|config_json| {
+                InstallOptions::from_config(&config_json, |name| env_var(&shell_env, name))
+            })
+            .transpose()
+            .map_err(with_version)?
+            .unwrap_or_default();
+        self.install_scope = install_options
+            .scope_to_worktree
+            .then(|| worktree_scope(&worktree.root_path()));
+        self.repository = install_options
+            .repository
+            .clone()
+            .unwrap_or_else(|| GITHUB_REPO.to_owned());
+        let version = self
+            .ensure_adapter_installed(&install_options)
+            .map_err(with_version)?;
+        let binary = self
+            .build_with_repair(&version, &install_options, |debugger| {
+                debugger.build_binary(
+                    &version,
+                    config.clone(),
+                    user_provided_debug_adapter_path.clone(),
+                    worktree,
+                )
+            })
+            .map_err(|err| with_version(err.into()))?;
+
+        self.record_resolved_config(&binary.request_args.configuration);
+        Ok(binary)
+    }
+
+    fn dap_request_kind(
+        &mut self,
+        adapter_name: String,
+        config: serde_json::Value,
+    ) -> Result<StartDebuggingRequestArgumentsRequest, String> {
+        validate_adapter_name_with(
+            &adapter_name,
+            &self.adapter_name,
+            AdapterNameMatching::from_config(&config),
+        )?;
+
+        request_type_from_config(&config)
+    }
+
+    fn dap_config_to_scenario(&mut self, config: DebugConfig) -> Result<DebugScenario, String> {
+        validate_adapter_name(&config.adapter, &self.adapter_name)?;
+
+        let label = if config.label.trim().is_empty() {
+            default_label(&config.request)
+        } else {
+            config.label
+        };
+
+        let scenario_config = match &config.request {
+            DebugRequest::Launch(launch) => {
+                let program =
+                    paths::expand_home(paths::trim_trailing(&launch.program), &user_home)?;
+                let cwd = launch
+                    .cwd
+                    .as_deref()
+                    .map(|cwd| {
+                        let cwd = paths::trim_trailing_separators(paths::trim_trailing(cwd));
+                        paths::expand_home(cwd, &user_home)
+                    })
+                    .transpose()?;
+                let args = launch
+                    .args
+                    .iter()
+                    .map(|arg| paths::expand_home(paths::trim_trailing(arg), &user_home))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // Validate program file exists
+                if !program.is_empty() && !Path::new(&program).exists() {
+                    return Err(format!(
+                        "Script file not found: '{}'. Check the 'program' path in your debug configuration.",
+                        program
+                    ));
+                }
+
+                let cwd = resolve_cwd(cwd.as_deref(), &program)?;
+
+                serde_json::json!({
+                    "request": "launch",
+                    "program": program,
+                    "cwd": cwd,
+                    "args": args,
+                    "stopOnEntry": config.stop_on_entry.unwrap_or(false),
+                    "port": DEFAULT_PORT,
+                })
+            }
+            DebugRequest::Attach(_) => {
+                return Err("AutoHotkey debugger does not support attach mode".into());
+            }
+        };
+
+        Ok(DebugScenario {
+            adapter: config.adapter,
+            label,
+            build: None,
+            // Pretty-printed (keys are already sorted) so saved scenarios are easy to read
+            config: serde_json::to_string_pretty(&scenario_config)
+                .map_err(|e| format!("Failed to serialize scenario config: {}", e))?,
+            tcp_connection: None,
+        })
+    }
+}
+
+zed::register_extension!(AutoHotkeyDebugger);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zed_extension_api::{AttachRequest, Extension, LaunchRequest};
+
+    // ==================== Adapter constants tests ====================
+
+    #[test]
+    fn adapter_constants_match_extension_manifest() {
+        // Arrange
+        let manifest = include_str!("../extension.toml");
+
+        // Act
+        let registered = manifest.contains(&format!("[debug_adapters.{}]", ADAPTER_NAME));
+
+        // Assert
+        assert_eq!(ADAPTER_NAME, "autohotkey");
+        assert!(registered);
+        assert_eq!(GITHUB_REPO, "alfredomtx/autohotkey-debug-adapter");
+    }
+
+    // ==================== diagnose_download_error tests ====================
+
+    #[test]
+    fn diagnose_download_error_classifies_common_failures() {
+        let cases = [
+            (
+                "failed to lookup address information: No such host is known",
+                "could not be resolved",
+            ),
+            (
+                "invalid peer certificate: UnknownIssuer",
+                "TLS handshake failed",
+            ),
+            ("HTTP status 404 Not Found", "moved or deleted"),
+            ("HTTP status 403: API rate limit exceeded", "rate limiting"),
+        ];
+
+        for (error, expected) in cases {
+            // Arrange
+            let url = "https://github.com/x/y/releases/download/v1/a.vsix";
+
+            // Act
+            let result = diagnose_download_error(url, error);
+
+            // Assert
+            assert!(result.contains(expected), "{} -> {}", error, result);
+            assert!(result.contains(url));
+            assert!(result.contains(error));
+        }
+    }
+
+    #[test]
+    fn diagnose_download_error_keeps_unrecognized_error() {
+        // Arrange
+        let url = "https://example.com/a.vsix";
+
+        // Act
+        let result = diagnose_download_error(url, "connection reset by peer");
+
+        // Assert
+        assert_eq!(
+            result,
+            "Failed to download debug adapter from 'https://example.com/a.vsix': connection reset by peer"
+        );
+    }
+
+    // ==================== Limits tests ====================
+
+    #[test]
+    fn limits_fill_defaults_for_missing_fields() {
+        // Arrange
+        let config = serde_json::json!({"connectTimeoutMs": 2500});
+
+        // Act
+        let result = Limits::from_config(&config).unwrap();
+
+        // Assert
+        assert_eq!(result.connect_timeout, Duration::from_millis(2500));
+        assert_eq!(result.network_timeout, Limits::default().network_timeout);
+        assert_eq!(
+            result.max_download_size,
+            Limits::default().max_download_size
+        );
+    }
+
+    #[test]
+    fn limits_parse_every_field() {
+        // Arrange
+        let config = serde_json::json!({
+            "networkTimeoutMs": 30000,
+            "connectTimeoutMs": 5000,
+            "maxDownloadSizeBytes": 1024,
+        });
+
+        // Act
+        let result = Limits::from_config(&config).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            Limits {
+                network_timeout: Duration::from_secs(30),
+                connect_timeout: Duration::from_secs(5),
+                max_download_size: 1024,
+            }
+        );
+    }
+
+    #[test]
+    fn limits_reject_invalid_values() {
+        // Arrange
+        let config = serde_json::json!({"maxDownloadSizeBytes": -1});
+
+        // Act
+        let result = Limits::from_config(&config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'maxDownloadSizeBytes'"));
+    }
+
+    // ==================== request_type_from_config tests ====================
+
+    #[test]
+    fn request_type_from_config_returns_launch_for_launch_request() {
+        // Arrange
+        let config = serde_json::json!({"request": "launch"});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    #[test]
+    fn request_type_from_config_returns_attach_for_attach_request() {
+        // Arrange
+        let config = serde_json::json!({"request": "attach"});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Attach)
+        ));
+    }
+
+    #[test]
+    fn request_type_from_config_returns_error_for_invalid_request() {
+        // Arrange
+        let config = serde_json::json!({"request": "invalid"});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Invalid request type"));
+    }
+
+    #[test]
+    fn request_type_from_config_defaults_to_launch_when_missing() {
+        // Arrange
+        let config = serde_json::json!({});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    #[test]
+    fn request_type_from_config_defaults_to_launch_when_null() {
+        // Arrange
+        let config = serde_json::json!({"request": null});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    #[test]
+    fn request_type_from_config_uses_default_request_when_missing() {
+        // Arrange
+        let config = serde_json::json!({"defaultRequest": "attach"});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Attach)
+        ));
+    }
+
+    #[test]
+    fn request_type_from_config_prefers_request_over_default_request() {
+        // Arrange
+        let config = serde_json::json!({"request": "launch", "defaultRequest": "attach"});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    #[test]
+    fn request_type_from_config_rejects_invalid_default_request() {
+        // Arrange
+        let config = serde_json::json!({"defaultRequest": "run"});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("Invalid defaultRequest type"));
+    }
+
+    #[test]
+    fn request_type_from_config_rejects_attach_when_only_launch_allowed() {
+        // Arrange
+        let config = serde_json::json!({"request": "attach", "allowedRequests": ["launch"]});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("'attach' is not permitted by policy"));
+        assert!(err.contains("allows: launch"));
+    }
+
+    #[test]
+    fn request_type_from_config_accepts_allowed_request() {
+        // Arrange
+        let config = serde_json::json!({"allowedRequests": ["launch"]});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    #[test]
+    fn request_type_from_config_rejects_unknown_allowed_request() {
+        // Arrange
+        let config = serde_json::json!({"allowedRequests": ["launch", "run"]});
+
+        // Act
+        let result = request_type_from_config(&config);
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .contains("Invalid allowedRequests type 'run'"));
+    }
+
+    #[test]
+    fn dap_request_kind_rejects_attach_when_only_launch_allowed() {
+        // Arrange
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = serde_json::json!({"request": "attach", "allowedRequests": ["launch"]});
+
+        // Act
+        let result = debugger.dap_request_kind("autohotkey".to_string(), config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("not permitted by policy"));
+    }
+
+    // ==================== validate_adapter_name tests ====================
+
+    #[test]
+    fn validate_adapter_name_accepts_autohotkey() {
+        // Arrange
+        let name = "autohotkey";
+
+        // Act
+        let result = validate_adapter_name(name, ADAPTER_NAME);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn validate_adapter_name_rejects_other_names() {
+        // Arrange
+        let name = "python";
+
+        // Act
+        let result = validate_adapter_name(name, ADAPTER_NAME);
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Unsupported adapter"));
+    }
+
+    #[test]
+    fn validate_adapter_name_rejects_empty_string() {
+        // Arrange
+        let name = "";
+
+        // Act
+        let result = validate_adapter_name(name, ADAPTER_NAME);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn validate_adapter_name_is_case_sensitive() {
+        // Arrange
+        let name = "AutoHotkey";
+
+        // Act
+        let result = validate_adapter_name(name, ADAPTER_NAME);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn validate_adapter_name_with_accepts_prefixed_name_when_tolerant() {
+        // Arrange
+        let name = "myext.autohotkey";
+
+        // Act
+        let result = validate_adapter_name_with(name, ADAPTER_NAME, AdapterNameMatching::Tolerant);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn validate_adapter_name_with_rejects_prefixed_name_when_strict() {
+        // Arrange
+        let name = "myext.autohotkey";
+
+        // Act
+        let result = validate_adapter_name_with(name, ADAPTER_NAME, AdapterNameMatching::Strict);
+
+        // Assert
+        assert!(result.unwrap_err().contains("Unsupported adapter"));
+    }
+
+    #[test]
+    fn validate_adapter_name_with_rejects_other_adapter_when_tolerant() {
+        for name in ["myext.python", ".autohotkey", "autohotkey.python"] {
+            // Act
+            let result =
+                validate_adapter_name_with(name, ADAPTER_NAME, AdapterNameMatching::Tolerant);
+
+            // Assert
+            assert!(result.is_err(), "expected {} to be rejected", name);
+        }
+    }
+
+    #[test]
+    fn with_adapter_name_changes_validation_and_install_paths() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        // Act
+        let mut debugger =
+            AutoHotkeyDebugger::with_base_dir(temp_dir.path()).with_adapter_name("autohotkey-h");
+
+        // Assert
+        assert!(debugger
+            .dap_request_kind("autohotkey-h".to_string(), serde_json::json!({}))
+            .is_ok());
+        assert!(debugger
+            .dap_request_kind(ADAPTER_NAME.to_string(), serde_json::json!({}))
+            .unwrap_err()
+            .contains("expected 'autohotkey-h'"));
+        assert_eq!(
+            Path::new(&debugger.adapter_dir()),
+            temp_dir.path().join("autohotkey-h")
+        );
+        assert_eq!(
+            Path::new(&debugger.versioned_dir("1.2.0")),
+            temp_dir
+                .path()
+                .join("autohotkey-h")
+                .join("autohotkey-h_1.2.0")
+        );
+    }
+
+    #[test]
+    fn with_adapter_name_finds_installs_under_custom_name() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let versioned_dir = temp_dir
+            .path()
+            .join("autohotkey-h")
+            .join("autohotkey-h_1.0.0");
+        fake_extract("", &versioned_dir.to_string_lossy()).unwrap();
+        install_version(temp_dir.path(), "1.1.0");
+        let debugger =
+            AutoHotkeyDebugger::with_base_dir(temp_dir.path()).with_adapter_name("autohotkey-h");
+
+        // Act
+        let result = debugger.cached_versions();
+
+        // Assert
+        assert_eq!(result, vec!["1.0.0".to_string()]);
+    }
+
+    #[test]
+    fn adapter_name_matching_defaults_to_strict() {
+        // Arrange
+        let config = serde_json::json!({});
+
+        // Act
+        let result = AdapterNameMatching::from_config(&config);
+
+        // Assert
+        assert_eq!(result, AdapterNameMatching::Strict);
+    }
+
+    #[test]
+    fn dap_request_kind_accepts_prefixed_name_in_tolerant_mode() {
+        // Arrange
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = serde_json::json!({"adapterNameMatching": "tolerant"});
+
+        // Act
+        let result = debugger.dap_request_kind("myext.autohotkey".to_string(), config);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    // ==================== parse_request_kind tests ====================
+
+    #[test]
+    fn parse_request_kind_defaults_empty_config_to_launch() {
+        // Arrange
+        let json = "";
+
+        // Act
+        let result = AutoHotkeyDebugger::parse_request_kind(json);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    #[test]
+    fn parse_request_kind_parses_valid_json() {
+        // Arrange
+        let json = r#"{"request": "launch"}"#;
+
+        // Act
+        let result = AutoHotkeyDebugger::parse_request_kind(json);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    #[test]
+    fn parse_request_kind_returns_error_for_invalid_json() {
+        // Arrange
+        let json = "not valid json";
+
+        // Act
+        let result = AutoHotkeyDebugger::parse_request_kind(json);
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Failed to parse config JSON"));
+    }
+
+    #[test]
+    fn parse_request_kind_accepts_bom_prefixed_json() {
+        // Arrange
+        let json = "\u{feff}{\"request\": \"attach\"}";
+
+        // Act
+        let result = AutoHotkeyDebugger::parse_request_kind(json);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Attach)
+        ));
+    }
+
+    #[test]
+    fn parse_request_kind_handles_empty_json_object() {
+        // Arrange
+        let json = "{}";
+
+        // Act
+        let result = AutoHotkeyDebugger::parse_request_kind(json);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Ok(StartDebuggingRequestArgumentsRequest::Launch)
+        ));
+    }
+
+    // ==================== Proxy detection tests ====================
+
+    #[test]
+    fn detect_proxy_prefers_config_value() {
+        // Arrange
+        let env_lookup = |_: &str| Some("http://env-proxy:8080".to_string());
+
+        // Act
+        let result = detect_proxy(Some("http://config-proxy:3128"), env_lookup);
+
+        // Assert
+        assert_eq!(result.as_deref(), Some("http://config-proxy:3128"));
+    }
+
+    #[test]
+    fn detect_proxy_falls_back_to_env_vars() {
+        // Arrange
+        let env_lookup =
+            |name: &str| (name == "HTTP_PROXY").then(|| "http://env-proxy:8080".to_string());
+
+        // Act
+        let result = detect_proxy(None, env_lookup);
+
+        // Assert
+        assert_eq!(result.as_deref(), Some("http://env-proxy:8080"));
+    }
+
+    #[test]
+    fn detect_proxy_returns_none_without_proxy() {
+        // Arrange
+        let env_lookup = |_: &str| None;
+
+        // Act
+        let result = detect_proxy(None, env_lookup);
+
+        // Assert
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn proxy_guidance_mentions_zed_proxy_setting() {
+        // Arrange
+        let proxy = "http://proxy:3128";
+
+        // Act
+        let result = proxy_guidance(proxy);
+
+        // Assert
+        assert!(result.contains("http://proxy:3128"));
+        assert!(result.contains("settings.json"));
+    }
+
+    // ==================== find_cached_version tests ====================
+
+    #[test]
+    fn find_cached_version_returns_newest_version() {
+        // Arrange
+        let entries = vec![
+            "autohotkey_1.0.0".to_string(),
+            "autohotkey_1.2.0".to_string(),
+            "other".to_string(),
+        ];
+
+        // Act
+        let result = find_cached_version(
+            ADAPTER_NAME,
+            || entries.clone(),
+            |_| true,
+            1,
+            Duration::ZERO,
+        );
+
+        // Assert
+        assert_eq!(result, CachedVersion::Complete("1.2.0".to_string()));
+    }
+
+    #[test]
+    fn find_cached_version_compares_versions_numerically() {
+        // Arrange
+        let entries = vec![
+            "autohotkey_1.9.0".to_string(),
+            "autohotkey_1.10.0".to_string(),
+        ];
+
+        // Act
+        let result = find_cached_version(
+            ADAPTER_NAME,
+            || entries.clone(),
+            |_| true,
+            1,
+            Duration::ZERO,
+        );
+
+        // Assert
+        assert_eq!(result, CachedVersion::Complete("1.10.0".to_string()));
+    }
+
+    #[test]
+    fn find_cached_version_retries_until_version_appears() {
+        // Arrange
+        let mut reads = 0;
+        let list_dir = || {
+            reads += 1;
+            if reads < 2 {
+                vec![]
+            } else {
+                vec!["autohotkey_1.0.0".to_string()]
+            }
+        };
+
+        // Act
+        let result = find_cached_version(ADAPTER_NAME, list_dir, |_| true, 3, Duration::ZERO);
+
+        // Assert
+        assert_eq!(result, CachedVersion::Complete("1.0.0".to_string()));
+        assert_eq!(reads, 2);
+    }
+
+    #[test]
+    fn find_cached_version_gives_up_after_attempts() {
+        // Arrange
+        let mut reads = 0;
+        let list_dir = || {
+            reads += 1;
+            vec![]
+        };
+
+        // Act
+        let result = find_cached_version(ADAPTER_NAME, list_dir, |_| true, 2, Duration::ZERO);
+
+        // Assert
+        assert_eq!(result, CachedVersion::Missing);
+        assert_eq!(reads, 2);
+    }
+
+    #[test]
+    fn find_cached_version_skips_incomplete_installs() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let complete = temp_dir.path().join("autohotkey_1.0.0");
+        write_file(&complete.join(AHK_EXE_SUBPATH));
+        write_file(&complete.join(ADAPTER_SCRIPT_SUBPATH));
+        let incomplete = temp_dir.path().join("autohotkey_2.0.0");
+        write_file(&incomplete.join(ADAPTER_SCRIPT_SUBPATH));
+        let adapter_dir = temp_dir.path().to_string_lossy().into_owned();
+
+        // Act
+        let result = find_cached_version(
+            ADAPTER_NAME,
+            || list_dir_names(&adapter_dir),
+            |version| {
+                adapter_paths_in(&temp_dir.path().join(format!("autohotkey_{}", version)))
+                    .is_complete()
+            },
+            1,
+            Duration::ZERO,
+        );
+
+        // Assert
+        assert_eq!(result, CachedVersion::Complete("1.0.0".to_string()));
+    }
+
+    #[test]
+    fn find_cached_version_reports_when_all_installs_are_incomplete() {
+        // Arrange
+        let entries = vec![
+            "autohotkey_1.1.0".to_string(),
+            "autohotkey_1.0.0".to_string(),
+        ];
+
+        // Act
+        let result = find_cached_version(
+            ADAPTER_NAME,
+            || entries.clone(),
+            |_| false,
+            2,
+            Duration::ZERO,
+        );
+
+        // Assert
+        assert_eq!(
+            result,
+            CachedVersion::Incomplete(vec!["1.0.0".to_string(), "1.1.0".to_string()])
+        );
+    }
+
+    // ==================== remove_dir_with_retry tests ====================
+
+    #[test]
+    fn remove_dir_with_retry_succeeds_after_transient_failure() {
+        // Arrange
+        let mut calls = 0;
+        let remove = |_: &Path| {
+            calls += 1;
+            if calls == 1 {
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, "locked"))
+            } else {
+                Ok(())
+            }
+        };
+
+        // Act
+        let result = remove_dir_with_retry(Path::new("adapter"), remove, 3, Duration::ZERO);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn remove_dir_with_retry_reports_possible_file_lock() {
+        // Arrange
+        let remove = |_: &Path| {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "access denied",
+            ))
+        };
+
+        // Act
+        let result = remove_dir_with_retry(Path::new("adapter"), remove, 2, Duration::ZERO);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("adapter"));
+        assert!(err.contains("antivirus"));
+        assert!(err.contains("access denied"));
+    }
+
+    #[test]
+    fn remove_dir_with_retry_treats_missing_dir_as_removed() {
+        // Arrange
+        let remove = |_: &Path| Err(io::Error::from(io::ErrorKind::NotFound));
+
+        // Act
+        let result = remove_dir_with_retry(Path::new("adapter"), remove, 3, Duration::ZERO);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn install_options_rejects_zero_cache_discovery_attempts() {
+        // Arrange
+        let config = serde_json::json!({"cacheDiscoveryAttempts": 0});
+
+        // Act
+        let result = InstallOptions::from_config(&config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(
+            result.cache_discovery_attempts,
+            DEFAULT_CACHE_DISCOVERY_ATTEMPTS
+        );
+    }
+
+    // ==================== Environment forwarding tests ====================
+
+    #[test]
+    fn merge_envs_config_values_override_inherited() {
+        // Arrange
+        let inherited = vec![
+            ("PATH".to_string(), "C:/Windows".to_string()),
+            ("MODE".to_string(), "inherited".to_string()),
+        ];
+        let config = serde_json::json!({"env": {"MODE": "config", "EXTRA": "1"}});
+
+        // Act
+        let result = merge_envs(inherited, &config).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            vec![
+                ("PATH".to_string(), "C:/Windows".to_string()),
+                ("MODE".to_string(), "config".to_string()),
+                ("EXTRA".to_string(), "1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_envs_returns_inherited_without_config_env() {
+        // Arrange
+        let inherited = vec![("PATH".to_string(), "C:/Windows".to_string())];
+        let config = serde_json::json!({});
+
+        // Act
+        let result = merge_envs(inherited.clone(), &config).unwrap();
+
+        // Assert
+        assert_eq!(result, inherited);
+    }
+
+    #[test]
+    fn merge_envs_rejects_non_string_values() {
+        // Arrange
+        let config = serde_json::json!({"env": {"COUNT": 1}});
+
+        // Act
+        let result = merge_envs(vec![], &config);
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("COUNT"));
+    }
+
+    #[test]
+    fn port_from_env_reads_named_variable() {
+        // Arrange
+        let envs = vec![("CI_AHK_PORT".to_string(), " 9123 ".to_string())];
+        let config = serde_json::json!({"port": 9005, "portEnv": "CI_AHK_PORT"});
+
+        // Act
+        let result = port_from_env(&config, &envs);
+
+        // Assert
+        assert_eq!(result, Ok(Some(9123)));
+    }
+
+    #[test]
+    fn port_from_env_is_none_without_port_env() {
+        // Arrange
+        let envs = vec![("CI_AHK_PORT".to_string(), "9123".to_string())];
+        let config = serde_json::json!({"port": 9005});
+
+        // Act
+        let result = port_from_env(&config, &envs);
+
+        // Assert
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn port_from_env_errors_for_missing_variable() {
+        // Arrange
+        let config = serde_json::json!({"portEnv": "CI_AHK_PORT"});
+
+        // Act
+        let result = port_from_env(&config, &vec![]);
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .contains("'CI_AHK_PORT', which is not set"));
+    }
+
+    #[test]
+    fn port_from_env_errors_for_invalid_port() {
+        // Arrange
+        let envs = vec![("CI_AHK_PORT".to_string(), "70000".to_string())];
+        let config = serde_json::json!({"portEnv": "CI_AHK_PORT"});
+
+        // Act
+        let result = port_from_env(&config, &envs);
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .contains("Invalid 'CI_AHK_PORT' value '70000'"));
+    }
+
+    #[test]
+    fn apply_lib_path_adds_expanded_env_entry() {
+        // Arrange
+        let envs = vec![("PATH".to_string(), "C:/Windows".to_string())];
+        let config = serde_json::json!({"libPath": "${workspaceFolder}/lib"});
+
+        // Act
+        let result = apply_lib_path(envs, &config, "C:/projects/app", &|| None).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            vec![
+                ("PATH".to_string(), "C:/Windows".to_string()),
+                (LIB_PATH_ENV.to_string(), "C:/projects/app/lib".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_lib_path_overrides_existing_entry() {
+        // Arrange
+        let envs = vec![(LIB_PATH_ENV.to_string(), "C:/old".to_string())];
+        let config = serde_json::json!({"libPath": "C:/new"});
+
+        // Act
+        let result = apply_lib_path(envs, &config, "C:/projects/app", &|| None).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            vec![(LIB_PATH_ENV.to_string(), "C:/new".to_string())]
+        );
+    }
+
+    #[test]
+    fn apply_lib_path_rejects_non_string() {
+        // Arrange
+        let config = serde_json::json!({"libPath": ["C:/lib"]});
+
+        // Act
+        let result = apply_lib_path(vec![], &config, "C:/projects/app", &|| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'libPath'"));
+    }
+
+    #[test]
+    fn data_dir_is_created_and_added_as_env_entry() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace = temp_dir.path().to_string_lossy().into_owned();
+        let envs = vec![(DATA_DIR_ENV.to_string(), "C:/old".to_string())];
+        let config = serde_json::json!({"dataDir": "${workspaceFolder}/.ahk/state"});
+
+        // Act
+        let data_dir = resolve_data_dir(&config, &workspace, &|| None).unwrap();
+        create_data_dir(data_dir.as_deref()).unwrap();
+        let result = apply_data_dir(envs, data_dir.as_deref());
+
+        // Assert
+        let expected = temp_dir.path().join(".ahk").join("state");
+        assert!(expected.is_dir());
+        assert_eq!(
+            result,
+            vec![(
+                DATA_DIR_ENV.to_string(),
+                expected.to_string_lossy().into_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn resolve_data_dir_refuses_relative_directory_outside_workspace() {
+        // Arrange
+        let config = serde_json::json!({"dataDir": "../state"});
+
+        // Act
+        let result = resolve_data_dir(&config, "/work/project", &|| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("outside '/work/project'"));
+    }
+
+    #[test]
+    fn resolve_data_dir_keeps_absolute_directory_outside_workspace() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let outside = temp_dir.path().join("AppData").join("MyScript");
+        let config = serde_json::json!({"dataDir": outside.to_string_lossy()});
+
+        // Act
+        let result = resolve_data_dir(&config, "/work/project", &|| None);
+
+        // Assert
+        assert_eq!(result, Ok(Some(outside)));
+    }
+
+    #[test]
+    fn script_envs_computes_environment_without_touching_disk() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace = temp_dir.path().to_string_lossy().into_owned();
+        let shell_env = vec![("PATH".to_string(), "C:/Windows".to_string())];
+        let config = serde_json::json!({
+            "env": {"MODE": "debug"},
+            "dataDir": "${workspaceFolder}/.ahk",
+        });
+
+        // Act
+        let (envs, data_dir) = script_envs(&config, shell_env, &workspace).unwrap();
+
+        // Assert
+        let expected = temp_dir.path().join(".ahk");
+        assert_eq!(data_dir.as_deref(), Some(expected.as_path()));
+        assert!(envs.contains(&("MODE".to_string(), "debug".to_string())));
+        assert!(envs.iter().any(|(name, _)| name == DATA_DIR_ENV));
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn diagnostic_env_includes_only_allowlisted_keys() {
+        // Arrange
+        let env_lookup = |name: &str| match name {
+            "PATH" => Some("C:/Windows".to_string()),
+            "TEMP" => Some("C:/Temp".to_string()),
+            "GITHUB_TOKEN" => Some("ghp_abc".to_string()),
+            "USERNAME" => Some("dev".to_string()),
+            _ => None,
+        };
+
+        // Act
+        let result = diagnostic_env(env_lookup);
+
+        // Assert
+        assert_eq!(
+            result,
+            vec![
+                ("PATH".to_string(), "C:/Windows".to_string()),
+                ("TEMP".to_string(), "C:/Temp".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn mask_secret_envs_hides_secret_values_only() {
+        // Arrange
+        let envs = vec![
+            ("GITHUB_TOKEN".to_string(), "ghp_abc".to_string()),
+            ("db_password".to_string(), "hunter2".to_string()),
+            ("CLIENT_SECRET".to_string(), "s3cr3t".to_string()),
+            ("PATH".to_string(), "C:/Windows".to_string()),
+        ];
+
+        // Act
+        let result = mask_secret_envs(envs);
+
+        // Assert
+        assert_eq!(result[0].1, MASKED_ENV_VALUE);
+        assert_eq!(result[1].1, MASKED_ENV_VALUE);
+        assert_eq!(result[2].1, MASKED_ENV_VALUE);
+        assert_eq!(result[3].1, "C:/Windows");
+    }
+
+    // ==================== adapter_configuration tests ====================
+
+    #[test]
+    fn adapter_configuration_strips_extension_only_keys() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = debugger
+            .resolve_config(
+                r#"{
+                    "program": "C:/scripts/main.ahk",
+                    "exeSubpath": "bin/AutoHotkey64.exe",
+                    "assetName": "autohotkey-debug.vsix",
+                    "maxCachedVersions": 2,
+                    "allowInsecureDownloads": false
+                }"#,
+                |_| None,
+            )
+            .unwrap();
+
+        // Act
+        let result = adapter_configuration(&config);
+
+        // Assert
+        for key in EXTENSION_ONLY_KEYS {
+            assert!(
+                result.get(key).is_none(),
+                "'{}' was sent to the adapter",
+                key
+            );
+        }
+        assert_eq!(result["program"], "C:/scripts/main.ahk");
+        assert_eq!(result["port"], DEFAULT_PORT);
+        assert_eq!(result["trace"], "off");
+    }
+
+    // ==================== resolve_config tests ====================
+
+    #[test]
+    fn resolve_config_injects_default_port() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["port"], DEFAULT_PORT);
+    }
+
+    #[test]
+    fn resolve_config_accepts_bom_prefixed_json() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = "\u{feff}{\"program\": \"C:/scripts/main.ahk\"}";
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["program"], "C:/scripts/main.ahk");
+    }
+
+    #[test]
+    fn resolve_config_keeps_user_port() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk", "port": 9100}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["port"], 9100);
+    }
+
+    #[test]
+    fn resolve_config_force_default_overwrites_user_port() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"port": 9100, "portMergeStrategy": "forceDefault"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["port"], DEFAULT_PORT);
+    }
+
+    #[test]
+    fn resolve_config_defaults_trace_to_off() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["trace"], "off");
+    }
+
+    #[test]
+    fn resolve_config_accepts_each_trace_level() {
+        for level in TRACE_LEVELS {
+            // Arrange
+            let debugger = AutoHotkeyDebugger::new();
+            let config = serde_json::json!({"trace": level}).to_string();
+
+            // Act
+            let result = debugger.resolve_config(&config, |_| None).unwrap();
+
+            // Assert
+            assert_eq!(result["trace"], level);
+        }
+    }
+
+    #[test]
+    fn resolve_config_rejects_invalid_trace_level() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"trace": "everything"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("'trace'"));
+    }
+
+    #[test]
+    fn resolve_config_rejects_invalid_adapter_version() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"adapterVersion": "latest"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'adapterVersion'"));
+    }
+
+    #[test]
+    fn resolve_config_rejects_invalid_adapter_repository() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"adapterRepository": "https://github.com/acme/fork"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'adapterRepository'"));
+    }
+
+    #[test]
+    fn resolve_config_omits_port_when_no_debug() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk", "noDebug": true, "port": 9100}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["noDebug"], true);
+        assert!(result.get("port").is_none());
+    }
+
+    #[test]
+    fn resolve_config_rejects_non_boolean_no_debug() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk", "noDebug": "yes"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'noDebug'"));
+    }
+
+    #[test]
+    fn resolve_config_defaults_output_capture_to_console() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["outputCapture"], "console");
+    }
+
+    #[test]
+    fn resolve_config_rejects_invalid_output_capture() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"outputCapture": "file"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'outputCapture'"));
+    }
+
+    #[test]
+    fn resolve_config_injects_follow_child_processes() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let default_config = r#"{"program": "C:/scripts/main.ahk"}"#;
+        let enabled_config = r#"{"program": "C:/scripts/main.ahk", "followChildProcesses": true}"#;
+
+        // Act
+        let default_result = debugger.resolve_config(default_config, |_| None).unwrap();
+        let enabled_result = debugger.resolve_config(enabled_config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(default_result["followChildProcesses"], false);
+        assert_eq!(enabled_result["followChildProcesses"], true);
+    }
+
+    #[test]
+    fn resolve_config_rejects_non_boolean_follow_child_processes() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"followChildProcesses": 1}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'followChildProcesses'"));
+    }
+
+    #[test]
+    fn resolve_config_terminate_on_exit_defaults_by_request_kind() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let launch = r#"{"request": "launch", "program": "C:/scripts/main.ahk"}"#;
+        let attach = r#"{"request": "attach"}"#;
+
+        // Act
+        let launch_result = debugger.resolve_config(launch, |_| None).unwrap();
+        let attach_result = debugger.resolve_config(attach, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(launch_result["terminateOnExit"], true);
+        assert_eq!(attach_result["terminateOnExit"], false);
+    }
+
+    #[test]
+    fn resolve_config_keeps_supplied_terminate_on_exit() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"request": "launch", "terminateOnExit": false}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["terminateOnExit"], false);
+    }
+
+    #[test]
+    fn resolve_config_rejects_non_boolean_terminate_on_exit() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"terminateOnExit": "always"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'terminateOnExit'"));
+    }
+
+    #[test]
+    fn resolve_config_passes_capability_overrides_through() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"capabilities": {"supportsConditionalBreakpoints": false, "supportsLogPoints": true}}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(
+            result["capabilities"],
+            serde_json::json!({"supportsConditionalBreakpoints": false, "supportsLogPoints": true})
+        );
+    }
+
+    #[test]
+    fn resolve_config_injects_connect_timeout() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(
+            result["connectTimeoutMs"],
+            Limits::default().connect_timeout.as_millis() as u64
+        );
+    }
+
+    #[test]
+    fn resolve_config_defaults_transport_to_stdio() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["transport"], "stdio");
+    }
+
+    #[test]
+    fn resolve_config_rejects_unknown_transport() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"transport": "pipe"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'transport'"));
+    }
+
+    #[test]
+    fn resolve_config_accepts_script_matching_runtime_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "#Requires AutoHotkey v2.0").unwrap();
+        let debugger = AutoHotkeyDebugger::new();
+        let config = serde_json::json!({
+            "program": script_path.to_string_lossy(),
+            "runtimeVersion": "v2",
+        });
+
+        // Act
+        let result = debugger.resolve_config(&config.to_string(), |_| None);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn resolve_config_skips_requires_check_without_runtime_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "#Requires AutoHotkey v2.0").unwrap();
+        let debugger = AutoHotkeyDebugger::new();
+        let config = serde_json::json!({"program": script_path.to_string_lossy()});
+
+        // Act
+        let result = debugger.resolve_config(&config.to_string(), |_| None);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn resolve_config_rejects_script_conflicting_with_runtime_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "#Requires AutoHotkey v2.0").unwrap();
+        let debugger = AutoHotkeyDebugger::new();
+        let config = serde_json::json!({
+            "program": script_path.to_string_lossy(),
+            "runtimeVersion": "v1",
+        });
+
+        // Act
+        let result = debugger.resolve_config(&config.to_string(), |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("requires AutoHotkey v2"));
+    }
+
+    #[test]
+    fn resolve_config_rejects_unknown_runtime_version() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"runtimeVersion": "v3"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'runtimeVersion'"));
+    }
+
+    #[test]
+    fn resolve_config_passes_watches_through() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"watches": ["A_Index", "config.path"]}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(
+            result["watches"],
+            serde_json::json!(["A_Index", "config.path"])
+        );
+    }
+
+    #[test]
+    fn resolve_config_accepts_each_encoding() {
+        for encoding in SCRIPT_ENCODINGS {
+            // Arrange
+            let debugger = AutoHotkeyDebugger::new();
+            let config = serde_json::json!({"encoding": encoding}).to_string();
+
+            // Act
+            let result = debugger.resolve_config(&config, |_| None).unwrap();
+
+            // Assert
+            assert_eq!(result["encoding"], encoding);
+        }
+    }
+
+    #[test]
+    fn resolve_config_leaves_encoding_to_adapter_by_default() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/main.ahk"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert!(result.get("encoding").is_none());
+    }
+
+    #[test]
+    fn resolve_config_rejects_unknown_encoding() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"encoding": "latin-9"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'encoding'"));
+    }
+
+    #[test]
+    fn resolve_config_defaults_exception_break_mode_to_unhandled() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = "{}";
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["exceptionBreakMode"], "unhandled");
+    }
+
+    #[test]
+    fn resolve_config_accepts_each_exception_break_mode() {
+        for mode in EXCEPTION_BREAK_MODES {
+            // Arrange
+            let debugger = AutoHotkeyDebugger::new();
+            let config = serde_json::json!({"exceptionBreakMode": mode}).to_string();
+
+            // Act
+            let result = debugger.resolve_config(&config, |_| None).unwrap();
+
+            // Assert
+            assert_eq!(result["exceptionBreakMode"], mode);
+        }
+    }
+
+    #[test]
+    fn resolve_config_rejects_unknown_exception_break_mode() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"exceptionBreakMode": "sometimes"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'exceptionBreakMode'"));
+    }
+
+    #[test]
+    fn resolve_config_passes_script_timeout_through() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"scriptTimeoutMs": 30000}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["scriptTimeoutMs"], 30000);
+    }
+
+    #[test]
+    fn resolve_config_rejects_non_positive_script_timeout() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"scriptTimeoutMs": 0}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'scriptTimeoutMs'"));
+    }
+
+    #[test]
+    fn resolve_config_passes_keep_alive_through() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"keepAliveMs": 15000}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["keepAliveMs"], 15000);
+    }
+
+    #[test]
+    fn resolve_config_omits_keep_alive_by_default() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = "{}";
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert!(result.get("keepAliveMs").is_none());
+    }
+
+    #[test]
+    fn resolve_config_rejects_non_positive_keep_alive() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"keepAliveMs": 0}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'keepAliveMs'"));
+    }
+
+    #[test]
+    fn resolve_config_passes_variable_depth_through() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"variableDepth": 3}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["variableDepth"], 3);
+    }
+
+    #[test]
+    fn resolve_config_rejects_non_positive_variable_depth() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"variableDepth": -1}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'variableDepth'"));
+    }
+
+    #[test]
+    fn resolve_config_defaults_restart_to_false() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = "{}";
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["restart"], false);
+    }
+
+    #[test]
+    fn resolve_config_injects_supplied_restart() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"restart": true}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["restart"], true);
+    }
+
+    #[test]
+    fn resolve_config_rejects_non_boolean_restart() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"restart": "always"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'restart'"));
+    }
+
+    #[test]
+    fn record_resolved_config_contains_injected_defaults() {
+        // Arrange
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = debugger
+            .resolve_config(r#"{"program": "C:/scripts/main.ahk"}"#, |_| None)
+            .unwrap();
+
+        // Act
+        debugger.record_resolved_config(&config.to_string());
+
+        // Assert
+        let resolved: serde_json::Value =
+            serde_json::from_str(debugger.resolved_config().unwrap()).unwrap();
+        assert_eq!(resolved["port"], DEFAULT_PORT);
+        assert_eq!(resolved["trace"], "off");
+        assert_eq!(resolved["program"], "C:/scripts/main.ahk");
+    }
+
+    #[test]
+    fn resolve_config_keeps_request_headers_from_adapter() {
+        // Arrange
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"requestHeaders": {"Authorization": "Bearer ghp_secret"}}"#;
+
+        // Act
+        let resolved = debugger.resolve_config(config, |_| None).unwrap();
+        debugger.record_resolved_config(&resolved.to_string());
+
+        // Assert
+        assert!(resolved.get("requestHeaders").is_none());
+        assert!(!debugger.resolved_config().unwrap().contains("ghp_secret"));
+    }
+
+    #[test]
+    fn record_resolved_config_masks_request_header_values() {
+        // Arrange
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"requestHeaders": {"Authorization": "Bearer ghp_secret"}}"#;
+
+        // Act
+        debugger.record_resolved_config(config);
+
+        // Assert
+        let resolved = debugger.resolved_config().unwrap();
+        assert!(!resolved.contains("ghp_secret"));
+        assert!(resolved.contains("Authorization"));
+    }
+
+    #[test]
+    fn record_resolved_config_masks_secret_env_values() {
+        // Arrange
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"env": {"API_TOKEN": "abc123", "MODE": "dev"}}"#;
+
+        // Act
+        debugger.record_resolved_config(config);
+
+        // Assert
+        let resolved = debugger.resolved_config().unwrap();
+        assert!(!resolved.contains("abc123"));
+        assert!(resolved.contains(MASKED_ENV_VALUE));
+        assert!(resolved.contains("dev"));
+    }
+
+    #[test]
+    fn resolve_config_resolves_file_dirname_cwd() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"program": "C:/scripts/tools/main.ahk", "cwd": "${fileDirname}"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["cwd"], "C:/scripts/tools");
+    }
+
+    #[test]
+    fn resolve_config_normalizes_attach_cwd_like_launch() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let launch = r#"{"request": "launch", "cwd": "~/scripts\r"}"#;
+        let attach = r#"{"request": "attach", "cwd": "~/scripts\r"}"#;
+        let env_lookup = |name: &str| (name == "HOME").then(|| "/home/dev".to_string());
+
+        // Act
+        let launch = debugger.resolve_config(launch, env_lookup).unwrap();
+        let attach = debugger.resolve_config(attach, env_lookup).unwrap();
+
+        // Assert
+        assert_eq!(attach["cwd"], "/home/dev/scripts");
+        assert!(!attach["cwd"].as_str().unwrap().ends_with('\r'));
+        assert_eq!(attach["cwd"], launch["cwd"]);
+    }
+
+    #[test]
+    fn resolve_config_trims_trailing_slash_from_cwd() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let with_slash = r#"{"cwd": "C:/scripts/"}"#;
+        let without_slash = r#"{"cwd": "C:/scripts"}"#;
+
+        // Act
+        let with_slash = debugger.resolve_config(with_slash, |_| None).unwrap();
+        let without_slash = debugger.resolve_config(without_slash, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(with_slash["cwd"], "C:/scripts");
+        assert_eq!(with_slash["cwd"], without_slash["cwd"]);
+    }
+
+    #[test]
+    fn resolve_config_resolves_file_dirname_cwd_on_attach() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"request": "attach", "program": "C:/scripts/tools/main.ahk", "cwd": "${fileDirname}"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["cwd"], "C:/scripts/tools");
+    }
+
+    // ==================== Compound config tests ====================
+
+    #[test]
+    fn validate_compound_returns_programs_for_valid_list() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let main = temp_dir.path().join("main.ahk");
+        let helper = temp_dir.path().join("helper.ahk");
+        std::fs::write(&main, "").unwrap();
+        std::fs::write(&helper, "").unwrap();
+        let config = serde_json::json!({
+            "compound": [main.to_string_lossy(), helper.to_string_lossy()],
+        });
+
+        // Act
+        let result = validate_compound(&config);
+
+        // Assert
+        let programs = result.unwrap().unwrap();
+        assert_eq!(programs.len(), 2);
+        assert!(programs[1].ends_with("helper.ahk"));
+    }
+
+    #[test]
+    fn validate_compound_rejects_missing_member() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let main = temp_dir.path().join("main.ahk");
+        std::fs::write(&main, "").unwrap();
+        let config = serde_json::json!({
+            "compound": [main.to_string_lossy(), "/nonexistent/helper.ahk"],
+        });
+
+        // Act
+        let result = validate_compound(&config);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("Script file not found"));
+        assert!(err.contains("compound[1]"));
+    }
+
+    #[test]
+    fn validate_compound_rejects_non_array() {
+        // Arrange
+        let config = serde_json::json!({"compound": "main.ahk"});
+
+        // Act
+        let result = validate_compound(&config);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn resolve_config_reports_compound_as_unsupported() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let main = temp_dir.path().join("main.ahk");
+        std::fs::write(&main, "").unwrap();
+        let debugger = AutoHotkeyDebugger::new();
+        let config = serde_json::json!({"compound": [main.to_string_lossy()]}).to_string();
+
+        // Act
+        let result = debugger.resolve_config(&config, |_| None);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("not supported yet"));
+        assert!(err.contains("main.ahk"));
+    }
+
+    // ==================== resolve_runtime_exe tests ====================
+
+    #[test]
+    fn resolve_runtime_exe_uses_bundled_exe_by_default() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(AHK_EXE_SUBPATH));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(result, Ok(paths.exe.clone()));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_prefers_default_runtime() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let bin_dir = temp_dir.path().join("extension/bin");
+        write_file(&bin_dir.join("AutoHotkey.exe"));
+        write_file(&bin_dir.join("AutoHotkey64.exe"));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(
+            result,
+            Ok(bin_dir
+                .join("AutoHotkey.exe")
+                .to_string_lossy()
+                .into_owned())
+        );
+    }
+
+    #[test]
+    fn resolve_runtime_exe_agrees_with_adapter_paths() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        let bin_dir = Path::new(&debugger.versioned_dir("1.2.0")).join("extension/bin");
+        write_file(&bin_dir.join("AutoHotkey64.exe"));
+        let config = serde_json::json!({});
+        let paths = debugger.configured_adapter_paths("1.2.0", &config).unwrap();
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(result, Ok(debugger.adapter_paths("1.2.0").exe));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_detects_only_runtime_present() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let bin_dir = temp_dir.path().join("extension/bin");
+        write_file(&bin_dir.join("AutoHotkey32.exe"));
+        write_file(&temp_dir.path().join(ADAPTER_SCRIPT_SUBPATH));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(
+            result,
+            Ok(bin_dir
+                .join("AutoHotkey32.exe")
+                .to_string_lossy()
+                .into_owned())
+        );
+    }
+
+    #[test]
+    fn resolve_runtime_exe_lists_known_runtimes_when_none_present() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join("extension/bin/README.txt"));
+        write_file(&temp_dir.path().join(ADAPTER_SCRIPT_SUBPATH));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(matches!(err, BuildError::MissingFiles(_)));
+        let err = err.to_string();
+        assert!(err.contains("No AutoHotkey runtime found"));
+        assert!(err.contains("AutoHotkey.exe, AutoHotkey64.exe, AutoHotkey32.exe"));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_picks_v1_runtime_for_runtime_version_v1() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let bin_dir = temp_dir.path().join("extension/bin");
+        write_file(&bin_dir.join("AutoHotkey.exe"));
+        write_file(&bin_dir.join("AutoHotkey64.exe"));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"runtimeVersion": "v1"});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(result, Ok(paths.exe.clone()));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_picks_v2_runtime_for_runtime_version_v2() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let bin_dir = temp_dir.path().join("extension/bin");
+        write_file(&bin_dir.join("AutoHotkey64.exe"));
+        write_file(&temp_dir.path().join(ADAPTER_SCRIPT_SUBPATH));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"runtimeVersion": "v2"});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(
+            result,
+            Ok(bin_dir
+                .join("AutoHotkey64.exe")
+                .to_string_lossy()
+                .into_owned())
+        );
+    }
+
+    #[test]
+    fn resolve_runtime_exe_reports_missing_runtime_for_runtime_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(AHK_EXE_SUBPATH));
+        write_file(&temp_dir.path().join(ADAPTER_SCRIPT_SUBPATH));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"runtimeVersion": "v2"});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(matches!(err, BuildError::Invalid(_)));
+        let err = err.to_string();
+        assert!(err.contains("No AutoHotkey v2 runtime found"));
+        assert!(err.contains("AutoHotkey64.exe, AutoHotkey32.exe"));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_uses_runtime_executable_when_bundled_disabled() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let runtime = temp_dir.path().join("AutoHotkey64.exe");
+        write_file(&runtime);
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({
+            "bundledRuntime": false,
+            "runtimeExecutable": runtime.to_string_lossy(),
+        });
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(result, Ok(runtime.to_string_lossy().into_owned()));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_uses_user_provided_path_when_bundled_disabled() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let runtime = temp_dir.path().join("AutoHotkey.exe");
+        write_file(&runtime);
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"bundledRuntime": false});
+
+        // Act
+        let result = resolve_runtime_exe(
+            &config,
+            Some(runtime.to_string_lossy().into_owned()),
+            &paths,
+            |_| None,
+        );
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn resolve_runtime_exe_errors_when_bundled_disabled_without_runtime() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(AHK_EXE_SUBPATH));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"bundledRuntime": false});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("'bundledRuntime' is false"));
+        assert!(err.contains("runtimeExecutable"));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_errors_for_missing_runtime_executable() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"runtimeExecutable": "/nonexistent/AutoHotkey.exe"});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("AutoHotkey runtime not found"));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_uses_custom_exe_name_in_bundled_bin() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let custom_exe = temp_dir
+            .path()
+            .join(AHK_EXE_SUBPATH)
+            .with_file_name("AutoHotkey64.exe");
+        write_file(&custom_exe);
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"exeName": "AutoHotkey64.exe"});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(result, Ok(custom_exe.to_string_lossy().into_owned()));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_errors_for_missing_custom_exe_name() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(AHK_EXE_SUBPATH));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"exeName": "AutoHotkey64.exe"});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("'AutoHotkey64.exe' not found"));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_uses_runtime_map_entry_for_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let v2_exe = temp_dir.path().join("v2").join("AutoHotkey64.exe");
+        write_file(&v2_exe);
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({
+            "runtimeVersion": "v2",
+            "runtimeMap": {"v2": v2_exe.to_string_lossy()},
+        });
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(result, Ok(v2_exe.to_string_lossy().into_owned()));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_falls_back_to_bundled_for_unmapped_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(AHK_EXE_SUBPATH));
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"runtimeMap": {"v2": "/nonexistent/AutoHotkey64.exe"}});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert_eq!(result, Ok(paths.exe.clone()));
+    }
+
+    #[test]
+    fn resolve_runtime_exe_errors_for_missing_mapped_runtime() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({"runtimeMap": {"v1": "/nonexistent/AutoHotkey.exe"}});
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("runtime for 'v1' not found"));
+        assert!(err.contains("'runtimeMap'"));
+    }
+
+    // ==================== System runtime discovery tests ====================
+
+    #[test]
+    fn system_runtime_candidates_use_environment_roots() {
+        // Arrange
+        let env_lookup = |name: &str| match name {
+            "ProgramFiles" => Some("D:/Apps".to_string()),
+            "LOCALAPPDATA" => Some("C:/Users/dev/AppData/Local".to_string()),
+            _ => None,
+        };
+
+        // Act
+        let result = system_runtime_candidates(env_lookup);
+
+        // Assert
+        assert_eq!(result.len(), 9);
+        assert_eq!(
+            result[0],
+            Path::new("D:/Apps")
+                .join("AutoHotkey")
+                .join("AutoHotkey.exe")
+        );
+        assert!(result[3].starts_with("C:\\Program Files (x86)"));
+        assert!(result[8].starts_with("C:/Users/dev/AppData/Local"));
+    }
+
+    #[test]
+    fn system_runtime_candidates_skip_unset_local_app_data() {
+        // Act
+        let result = system_runtime_candidates(|_| None);
+
+        // Assert
+        assert_eq!(result.len(), 6);
+    }
+
+    #[test]
+    fn find_system_runtime_returns_first_existing_candidate() {
+        // Arrange
+        let candidates = vec![
+            PathBuf::from("C:/Program Files/AutoHotkey/AutoHotkey.exe"),
+            PathBuf::from("C:/Program Files/AutoHotkey/AutoHotkeyU64.exe"),
+            PathBuf::from("C:/Program Files (x86)/AutoHotkey/AutoHotkey.exe"),
+        ];
+
+        // Act
+        let result = find_system_runtime(&candidates, |path| path != candidates[0]);
+
+        // Assert
+        assert_eq!(
+            result.as_deref(),
+            Some("C:/Program Files/AutoHotkey/AutoHotkeyU64.exe")
+        );
+    }
+
+    #[test]
+    fn find_system_runtime_returns_none_when_nothing_installed() {
+        // Arrange
+        let candidates = system_runtime_candidates(|_| None);
+
+        // Act
+        let result = find_system_runtime(&candidates, |_| false);
+
+        // Assert
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn resolve_runtime_exe_skips_exe_name_check_with_skip_binary_validation() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let paths = adapter_paths_in(temp_dir.path());
+        let config = serde_json::json!({
+            "exeName": "AutoHotkey64.exe",
+            "skipBinaryValidation": true,
+        });
+
+        // Act
+        let result = resolve_runtime_exe(&config, None, &paths, |_| None);
+
+        // Assert
+        assert!(result.unwrap().ends_with("AutoHotkey64.exe"));
+    }
+
+    #[test]
+    fn validate_exe_name_rejects_paths() {
+        for exe_name in ["bin/AutoHotkey.exe", "..\\AutoHotkey.exe", "..", ""] {
+            // Act
+            let result = validate_exe_name(exe_name);
+
+            // Assert
+            assert!(result.is_err(), "expected '{}' to be rejected", exe_name);
+        }
+    }
+
+    #[test]
+    fn validate_exe_name_accepts_bare_file_name() {
+        // Arrange
+        let exe_name = "AutoHotkey64.exe";
+
+        // Act
+        let result = validate_exe_name(exe_name);
+
+        // Assert
+        assert_eq!(result, Ok("AutoHotkey64.exe"));
+    }
+
+    // ==================== runtime_arguments tests ====================
+
+    #[test]
+    fn runtime_arguments_runs_adapter_script_by_default() {
+        // Arrange
+        let config = serde_json::json!({"program": "C:/scripts/main.ahk", "noDebug": false});
+
+        // Act
+        let result = runtime_arguments(&config, "C:/adapter/debugAdapter.ahk");
+
+        // Assert
+        assert_eq!(result, vec!["C:/adapter/debugAdapter.ahk"]);
+    }
+
+    #[test]
+    fn runtime_arguments_runs_program_directly_without_debugger() {
+        // Arrange
+        let config = serde_json::json!({
+            "program": "C:/scripts/main.ahk",
+            "args": ["--fast"],
+            "noDebug": true,
+        });
+
+        // Act
+        let result = runtime_arguments(&config, "C:/adapter/debugAdapter.ahk");
+
+        // Assert
+        assert_eq!(result, vec!["C:/scripts/main.ahk", "--fast"]);
+    }
+
+    #[test]
+    fn runtime_arguments_adds_error_stdout_for_std_capture() {
+        // Arrange
+        let config = serde_json::json!({"program": "C:/scripts/main.ahk", "outputCapture": "std"});
+
+        // Act
+        let result = runtime_arguments(&config, "C:/adapter/debugAdapter.ahk");
+
+        // Assert
+        assert_eq!(result, vec!["/ErrorStdOut", "C:/adapter/debugAdapter.ahk"]);
+    }
+
+    #[test]
+    fn runtime_arguments_adds_error_stdout_before_program_without_debugger() {
+        // Arrange
+        let config = serde_json::json!({
+            "program": "C:/scripts/main.ahk",
+            "noDebug": true,
+            "outputCapture": "std",
+        });
+
+        // Act
+        let result = runtime_arguments(&config, "C:/adapter/debugAdapter.ahk");
+
+        // Assert
+        assert_eq!(result, vec!["/ErrorStdOut", "C:/scripts/main.ahk"]);
+    }
+
+    #[test]
+    fn runtime_arguments_adds_code_page_before_adapter_script() {
+        // Arrange
+        let config = serde_json::json!({"outputCapture": "std", "codePage": 65001});
+
+        // Act
+        let result = runtime_arguments(&config, "C:/adapter/debugAdapter.ahk");
+
+        // Assert
+        assert_eq!(
+            result,
+            vec!["/ErrorStdOut", "/CP65001", "C:/adapter/debugAdapter.ahk"]
+        );
+    }
+
+    #[test]
+    fn runtime_arguments_omits_error_stdout_for_console_capture() {
+        // Arrange
+        let config = serde_json::json!({"outputCapture": "console"});
+
+        // Act
+        let result = runtime_arguments(&config, "C:/adapter/debugAdapter.ahk");
+
+        // Assert
+        assert!(!result.contains(&ERROR_STDOUT_FLAG.to_string()));
+    }
+
+    // ==================== default_cwd tests ====================
+
+    #[test]
+    fn default_cwd_uses_worktree_root() {
+        // Arrange
+        let process_cwd = || Some("C:/zed".to_string());
+
+        // Act
+        let result = default_cwd("C:/projects/app", "C:/scripts/main.ahk", process_cwd);
+
+        // Assert
+        assert_eq!(result, Ok("C:/projects/app".to_string()));
+    }
+
+    #[test]
+    fn default_cwd_falls_back_to_program_dir_when_root_is_empty() {
+        // Arrange
+        let process_cwd = || Some("C:/zed".to_string());
+
+        // Act
+        let result = default_cwd("", "C:/scripts/main.ahk", process_cwd);
+
+        // Assert
+        assert_eq!(result, Ok("C:/scripts".to_string()));
+    }
+
+    #[test]
+    fn default_cwd_falls_back_to_process_cwd() {
+        // Arrange
+        let process_cwd = || Some("C:/zed".to_string());
+
+        // Act
+        let result = default_cwd(" ", "main.ahk", process_cwd);
+
+        // Assert
+        assert_eq!(result, Ok("C:/zed".to_string()));
+    }
+
+    #[test]
+    fn default_cwd_errors_when_nothing_is_available() {
+        // Arrange
+        let process_cwd = || None;
+
+        // Act
+        let result = default_cwd("", "", process_cwd);
+
+        // Assert
+        assert!(result.unwrap_err().contains("Set 'cwd'"));
+    }
+
+    // ==================== validate_cwd tests ====================
+
+    #[test]
+    fn validate_cwd_accepts_existing_directory() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = serde_json::json!({});
+
+        // Act
+        let result = validate_cwd(&config, &temp_dir.path().to_string_lossy(), "");
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn validate_cwd_rejects_missing_directory() {
+        // Arrange
+        let config = serde_json::json!({});
+
+        // Act
+        let result = validate_cwd(&config, "/nonexistent/dir", "");
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("Working directory not found"));
+        assert!(err.contains("/nonexistent/dir"));
+    }
+
+    #[test]
+    fn validate_cwd_resolves_relative_directory_against_worktree() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(temp_dir.path().join("scripts")).unwrap();
+        let config = serde_json::json!({});
+
+        // Act
+        let result = validate_cwd(&config, "scripts", &temp_dir.path().to_string_lossy());
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn validate_cwd_skips_check_when_disabled() {
+        // Arrange
+        let config = serde_json::json!({"validateCwd": false});
+
+        // Act
+        let result = validate_cwd(&config, "/nonexistent/dir", "");
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn create_cwd_creates_missing_directory_when_enabled() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cwd = temp_dir.path().join("out").join("logs");
+        let config = serde_json::json!({"createCwd": true});
+
+        // Act
+        let result = create_cwd(
+            &config,
+            &cwd.to_string_lossy(),
+            &temp_dir.path().to_string_lossy(),
+        );
+
+        // Assert
+        assert_eq!(result, Ok(cwd.to_string_lossy().into_owned()));
+        assert!(cwd.is_dir());
+        assert!(validate_cwd(&config, &cwd.to_string_lossy(), "").is_ok());
+    }
+
+    #[test]
+    fn create_cwd_resolves_relative_directory_against_worktree() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = serde_json::json!({"createCwd": true});
+
+        // Act
+        let result = create_cwd(&config, "out", &temp_dir.path().to_string_lossy());
+
+        // Assert
+        let cwd = result.unwrap();
+        assert_eq!(Path::new(&cwd), temp_dir.path().join("out"));
+        assert!(validate_cwd(&config, "out", &temp_dir.path().to_string_lossy()).is_ok());
+    }
+
+    #[test]
+    fn create_cwd_leaves_missing_directory_by_default() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cwd = temp_dir.path().join("out");
+        let config = serde_json::json!({});
+
+        // Act
+        let result = create_cwd(
+            &config,
+            &cwd.to_string_lossy(),
+            &temp_dir.path().to_string_lossy(),
+        );
+
+        // Assert
+        assert_eq!(result, Ok(cwd.to_string_lossy().into_owned()));
+        assert!(!cwd.exists());
+        assert!(validate_cwd(&config, &cwd.to_string_lossy(), "")
+            .unwrap_err()
+            .contains("Working directory not found"));
+    }
+
+    #[test]
+    fn create_cwd_refuses_directory_outside_worktree() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let worktree = temp_dir.path().join("project");
+        let cwd = worktree.join("..").join("elsewhere");
+        let config = serde_json::json!({"createCwd": true});
+
+        // Act
+        let result = create_cwd(&config, &cwd.to_string_lossy(), &worktree.to_string_lossy());
+
+        // Assert
+        assert!(result.unwrap_err().contains("outside"));
+        assert!(!temp_dir.path().join("elsewhere").exists());
+    }
+
+    // ==================== adapter_connection tests ====================
+
+    #[test]
+    fn adapter_connection_is_none_for_stdio() {
+        // Arrange
+        let config = serde_json::json!({"transport": "stdio", "port": 9005});
+
+        // Act
+        let result = adapter_connection(&config);
+
+        // Assert
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn adapter_connection_targets_localhost_port_for_tcp() {
+        // Arrange
+        let config =
+            serde_json::json!({"transport": "tcp", "port": 9100, "connectTimeoutMs": 3000});
+
+        // Act
+        let result = adapter_connection(&config).unwrap().unwrap();
+
+        // Assert
+        assert_eq!(result.port, 9100);
+        assert_eq!(result.host, u32::from(std::net::Ipv4Addr::LOCALHOST));
+        assert_eq!(result.timeout, Some(3000));
+    }
+
+    #[test]
+    fn adapter_connection_rejects_invalid_port_for_tcp() {
+        // Arrange
+        let config = serde_json::json!({"transport": "tcp", "port": 70000});
+
+        // Act
+        let result = adapter_connection(&config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'port'"));
+    }
+
+    #[test]
+    fn scenario_connection_lets_config_port_override_scenario_default() {
+        // Arrange
+        let tcp = TcpArgumentsTemplate {
+            port: Some(DEFAULT_PORT),
+            host: None,
+            timeout: None,
+        };
+        let config = serde_json::json!({"port": 9100, "connectTimeoutMs": 3000});
+
+        // Act
+        let result = scenario_connection(&config, Some(&tcp)).unwrap().unwrap();
+
+        // Assert
+        assert_eq!(result.port, 9100);
+        assert_eq!(result.host, u32::from(std::net::Ipv4Addr::LOCALHOST));
+        assert_eq!(result.timeout, Some(3000));
+    }
+
+    #[test]
+    fn scenario_connection_falls_back_to_scenario_port() {
+        // Arrange
+        let tcp = TcpArgumentsTemplate {
+            port: Some(9200),
+            host: None,
+            timeout: Some(500),
+        };
+        let config = serde_json::json!({});
+
+        // Act
+        let result = scenario_connection(&config, Some(&tcp)).unwrap().unwrap();
+
+        // Assert
+        assert_eq!(result.port, 9200);
+        assert_eq!(result.timeout, Some(500));
+    }
+
+    #[test]
+    fn scenario_connection_is_none_without_scenario_tcp_or_in_no_debug() {
+        // Arrange
+        let tcp = TcpArgumentsTemplate {
+            port: Some(DEFAULT_PORT),
+            host: None,
+            timeout: None,
+        };
+
+        // Act
+        let stdio = scenario_connection(&serde_json::json!({"port": 9100}), None);
+        let no_debug = scenario_connection(&serde_json::json!({"noDebug": true}), Some(&tcp));
+
+        // Assert
+        assert!(stdio.unwrap().is_none());
+        assert!(no_debug.unwrap().is_none());
+    }
+
+    // ==================== default_label tests ====================
+
+    #[test]
+    fn default_label_uses_program_file_name_for_launch() {
+        // Arrange
+        let request = DebugRequest::Launch(LaunchRequest {
+            program: "C:/scripts/main.ahk".to_string(),
+            cwd: None,
+            args: vec![],
+            envs: vec![],
+        });
+
+        // Act
+        let result = default_label(&request);
+
+        // Assert
+        assert_eq!(result, "AutoHotkey: main.ahk");
+    }
+
+    #[test]
+    fn default_label_names_attach_sessions() {
+        // Arrange
+        let request = DebugRequest::Attach(AttachRequest { process_id: None });
+
+        // Act
+        let result = default_label(&request);
+
+        // Assert
+        assert_eq!(result, "AutoHotkey Attach");
+    }
+
+    // ==================== resolve_cwd tests ====================
+
+    #[test]
+    fn resolve_cwd_returns_program_parent_for_file_dirname() {
+        // Arrange
+        let program = "/home/user/scripts/main.ahk";
+
+        // Act
+        let result = resolve_cwd(Some(FILE_DIRNAME_VAR), program);
+
+        // Assert
+        assert_eq!(result.unwrap().as_deref(), Some("/home/user/scripts"));
+    }
+
+    #[test]
+    fn resolve_cwd_keeps_explicit_cwd() {
+        // Arrange
+        let program = "/home/user/scripts/main.ahk";
+
+        // Act
+        let result = resolve_cwd(Some("/work"), program);
+
+        // Assert
+        assert_eq!(result.unwrap().as_deref(), Some("/work"));
+    }
+
+    #[test]
+    fn resolve_cwd_errors_for_file_dirname_without_program() {
+        // Arrange
+        let program = "";
+
+        // Act
+        let result = resolve_cwd(Some(FILE_DIRNAME_VAR), program);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    // ==================== shell_command tests ====================
+
+    #[test]
+    fn shell_command_quotes_paths_with_spaces_on_windows() {
+        // Arrange
+        let arguments = vec![
+            "/ErrorStdOut".to_string(),
+            "C:\\Program Files\\adapter\\debugAdapter.ahk".to_string(),
+        ];
+
+        // Act
+        let result = shell_command("C:\\Program Files\\AutoHotkey.exe", &arguments, true);
+
+        // Assert
+        assert_eq!(
+            result,
+            r#""C:\Program Files\AutoHotkey.exe" /ErrorStdOut "C:\Program Files\adapter\debugAdapter.ahk""#
+        );
+    }
+
+    #[test]
+    fn shell_command_quotes_paths_with_spaces_on_unix() {
+        // Arrange
+        let arguments = vec![
+            "/home/dev/my scripts/main.ahk".to_string(),
+            "it's".to_string(),
+        ];
+
+        // Act
+        let result = shell_command("/opt/ahk/AutoHotkey.exe", &arguments, false);
+
+        // Assert
+        assert_eq!(
+            result,
+            r#"/opt/ahk/AutoHotkey.exe '/home/dev/my scripts/main.ahk' 'it'\''s'"#
+        );
+    }
+
+    #[test]
+    fn quote_shell_arg_escapes_quotes_and_trailing_backslashes_on_windows() {
+        // Act
+        let quoted_quote = quote_shell_arg(r#"say "hi""#, true);
+        let trailing_backslash = quote_shell_arg(r"C:\My Dir\", true);
+        let empty = quote_shell_arg("", true);
+
+        // Assert
+        assert_eq!(quoted_quote, r#""say \"hi\"""#);
+        assert_eq!(trailing_backslash, r#""C:\My Dir\\""#);
+        assert_eq!(empty, r#""""#);
+    }
+
+    // ==================== request_headers tests ====================
+
+    #[test]
+    fn request_headers_adds_github_token_as_authorization() {
+        // Arrange
+        let config = serde_json::json!({"requestHeaders": {"X-Mirror": "internal"}});
+        let env_lookup = |name: &str| (name == GITHUB_TOKEN_ENV).then(|| "ghp_secret".to_string());
+
+        // Act
+        let result = request_headers(&config, env_lookup);
+
+        // Assert
+        assert_eq!(
+            result,
+            Ok(vec![
+                ("X-Mirror".to_string(), "internal".to_string()),
+                ("Authorization".to_string(), "Bearer ghp_secret".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn request_headers_keeps_configured_authorization() {
+        // Arrange
+        let config = serde_json::json!({"requestHeaders": {"authorization": "token abc"}});
+        let env_lookup = |_: &str| Some("ghp_secret".to_string());
+
+        // Act
+        let result = request_headers(&config, env_lookup);
+
+        // Assert
+        assert_eq!(
+            result,
+            Ok(vec![("authorization".to_string(), "token abc".to_string())])
+        );
+    }
+
+    #[test]
+    fn request_headers_rejects_non_string_values() {
+        // Arrange
+        let config = serde_json::json!({"requestHeaders": {"X-Retries": 3}});
+
+        // Act
+        let result = request_headers(&config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'X-Retries'"));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_notes_unsent_headers_when_offline() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| Err("401 Unauthorized".to_string()));
+        let options = InstallOptions {
+            cache_discovery_attempts: 1,
+            request_header_names: vec!["Authorization".to_string()],
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("these were not sent: Authorization"));
+        assert!(!err.contains("Bearer"));
+    }
+
+    // ==================== user_agent tests ====================
+
+    #[test]
+    fn user_agent_uses_crate_version() {
+        // Arrange
+        let expected = format!("zed-autohotkey-debugger/{}", env!("CARGO_PKG_VERSION"));
+
+        // Act
+        let result = user_agent(None);
+
+        // Assert
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn with_version_appends_extension_version() {
+        // Arrange
+        let message = "Debug adapter script not found".to_string();
+
+        // Act
+        let result = with_version(message);
+
+        // Assert
+        assert_eq!(
+            result,
+            format!(
+                "Debug adapter script not found [zed-autohotkey-debugger/{}]",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn with_version_does_not_repeat_existing_tag() {
+        // Arrange
+        let message = format!("Failed to fetch release [{}]", user_agent(Some("ci")));
+
+        // Act
+        let result = with_version(message.clone());
+
+        // Assert
+        assert_eq!(result, message);
+    }
+
+    #[test]
+    fn user_agent_appends_suffix() {
+        // Arrange
+        let suffix = " acme-ci ";
+
+        // Act
+        let result = user_agent(Some(suffix));
+
+        // Assert
+        assert!(result.starts_with("zed-autohotkey-debugger/"));
+        assert!(result.ends_with(" acme-ci"));
+    }
+
+    #[test]
+    fn install_options_detects_proxy_from_shell_env() {
+        // Arrange
+        let env = vec![(
+            "HTTPS_PROXY".to_string(),
+            "http://env-proxy:8080".to_string(),
+        )];
+
+        // Act
+        let result =
+            InstallOptions::from_config(&serde_json::json!({}), |name| env_var(&env, name))
+                .unwrap();
+
+        // Assert
+        assert_eq!(result.proxy.as_deref(), Some("http://env-proxy:8080"));
+    }
+
+    #[test]
+    fn install_options_reads_github_token_from_shell_env() {
+        // Arrange
+        let env = vec![(GITHUB_TOKEN_ENV.to_string(), "ghp_secret".to_string())];
+
+        // Act
+        let result =
+            InstallOptions::from_config(&serde_json::json!({}), |name| env_var(&env, name))
+                .unwrap();
+
+        // Assert
+        assert_eq!(
+            result.request_header_names,
+            vec!["Authorization".to_string()]
+        );
+    }
+
+    #[test]
+    fn install_options_reads_user_agent_suffix() {
+        // Arrange
+        let config = serde_json::json!({"userAgentSuffix": "acme-ci"});
+
+        // Act
+        let result = InstallOptions::from_config(&config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result.user_agent_suffix.as_deref(), Some("acme-ci"));
+    }
+
+    #[test]
+    fn install_options_reads_version_and_repository_from_shell_env() {
+        // Arrange
+        let env = vec![
+            ("AHK_DEBUG_VERSION".to_string(), "v1.1.0".to_string()),
+            (
+                "AHK_DEBUG_REPO".to_string(),
+                "acme/autohotkey-debug".to_string(),
+            ),
+        ];
+
+        // Act
+        let result =
+            InstallOptions::from_config(&serde_json::json!({}), |name| env_var(&env, name))
+                .unwrap();
+
+        // Assert
+        assert_eq!(result.version.as_deref(), Some("1.1.0"));
+        assert_eq!(result.repository.as_deref(), Some("acme/autohotkey-debug"));
+    }
+
+    #[test]
+    fn install_options_prefers_configured_version_over_shell_env() {
+        // Arrange
+        let config = serde_json::json!({"adapterVersion": "1.2.0"});
+        let env = vec![("AHK_DEBUG_VERSION".to_string(), "1.1.0".to_string())];
+
+        // Act
+        let result = InstallOptions::from_config(&config, |name| env_var(&env, name)).unwrap();
+
+        // Assert
+        assert_eq!(result.version.as_deref(), Some("1.2.0"));
+    }
+
+    #[test]
+    fn install_options_reads_resolution_order() {
+        // Arrange
+        let config = serde_json::json!({"resolutionOrder": "cache-first"});
+
+        // Act
+        let cache_first = InstallOptions::from_config(&config, |_| None).unwrap();
+        let default = InstallOptions::from_config(&serde_json::json!({}), |_| None).unwrap();
+
+        // Assert
+        assert!(cache_first.cache_first);
+        assert!(!default.cache_first);
+    }
+
+    #[test]
+    fn install_options_requires_opt_in_for_insecure_downloads() {
+        // Arrange
+        let opted_in = serde_json::json!({"allowInsecureDownloads": true});
+        let invalid = serde_json::json!({"allowInsecureDownloads": "yes"});
+
+        // Act
+        let default = InstallOptions::from_config(&serde_json::json!({}), |_| None).unwrap();
+        let opted_in = InstallOptions::from_config(&opted_in, |_| None).unwrap();
+        let invalid = InstallOptions::from_config(&invalid, |_| None);
+
+        // Assert
+        assert!(!default.allow_insecure_downloads);
+        assert!(opted_in.allow_insecure_downloads);
+        assert!(invalid.is_err());
+    }
+
+    // ==================== Install flow tests with injected dependencies ====================
+
+    fn release(version: &str, assets: &[(&str, &str)]) -> GithubRelease {
+        GithubRelease {
+            version: version.to_string(),
+            assets: assets
+                .iter()
+                .map(|(name, url)| GithubReleaseAsset {
+                    name: name.to_string(),
+                    download_url: url.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// A release for `tag` with the standard `.vsix` asset, for the tagged release fetcher.
+    fn tagged_release(_repository: &str, tag: &str) -> Result<GithubRelease, String> {
+        let version = tag.trim_start_matches('v');
+        Ok(release(
+            tag,
+            &[(
+                &format!("autohotkey-debug-{}.vsix", version),
+                &format!("https://example.com/{}.vsix", version),
+            )],
+        ))
+    }
+
+    /// Simulates extracting a `.vsix` by creating the expected layout under `dest`.
+    fn fake_extract(_url: &str, dest: &str) -> Result<(), String> {
+        for subpath in [AHK_EXE_SUBPATH, ADAPTER_SCRIPT_SUBPATH] {
+            let path = Path::new(dest).join(subpath);
+            write_file(&path);
+            std::fs::write(path, "stub").unwrap();
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn with_base_dir_places_installs_under_base_dir() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        // Act
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Assert
+        assert_eq!(
+            Path::new(&debugger.adapter_dir()),
+            temp_dir.path().join(ADAPTER_NAME)
+        );
+        assert!(Path::new(&debugger.temp_scripts_dir()).starts_with(temp_dir.path()));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_downloads_latest_release() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let downloads = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = downloads.clone();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(move |url, dest| {
+                recorded.lock().unwrap().push(url.to_string());
+                fake_extract(url, dest)
+            });
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+        assert_eq!(
+            *downloads.lock().unwrap(),
+            vec!["https://example.com/a.vsix".to_string()]
+        );
+        let paths = debugger.adapter_paths("1.2.0");
+        assert!(paths.is_complete());
+        assert!(Path::new(&paths.versioned_dir)
+            .join(INSTALL_SENTINEL)
+            .is_file());
+    }
+
+    #[test]
+    fn ensure_adapter_installed_installs_pinned_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| panic!("latest release must not be fetched"))
+            .with_tagged_release_fetcher(tagged_release)
+            .with_downloader(fake_extract);
+        let options = InstallOptions {
+            version: Some("1.1.0".to_string()),
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert_eq!(result, Ok("1.1.0".to_string()));
+        assert!(debugger.adapter_paths("1.1.0").is_complete());
+    }
+
+    #[test]
+    fn ensure_adapter_installed_fetches_from_configured_repository() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|repository| {
+                assert_eq!(repository, "acme/autohotkey-debug");
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(fake_extract);
+        debugger.repository = "acme/autohotkey-debug".to_string();
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+        assert!(debugger.adapter_dir().contains(REPOSITORY_SCOPES_DIR));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_trusts_cached_version_when_skipping_validation() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| panic!("the cached version must be used"));
+        debugger.cached_version.set("1.2.0".to_string()).unwrap();
+        let options = InstallOptions {
+            skip_validation: true,
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+        assert!(!Path::new(&debugger.versioned_dir("1.2.0")).exists());
+    }
+
+    #[test]
+    fn ensure_adapter_installed_reports_download_progress() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(fake_extract)
+            .with_progress_reporter(move |event| recorded.lock().unwrap().push(event.clone()));
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                DownloadEvent::Started {
+                    version: "1.2.0".to_string(),
+                    url: "https://example.com/a.vsix".to_string(),
+                },
+                DownloadEvent::Completed {
+                    version: "1.2.0".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ensure_adapter_installed_reports_failed_download() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(|_, _| Err("connection reset".to_string()))
+            .with_progress_reporter(move |event| recorded.lock().unwrap().push(event.clone()));
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        let events = events.lock().unwrap();
+        assert!(result.is_err());
+        assert_eq!(events.len(), 2);
+        assert!(
+            matches!(&events[1], DownloadEvent::Failed { error, .. } if error.contains("connection reset"))
+        );
+    }
+
+    #[test]
+    fn ensure_adapter_installed_downloads_asset_named_by_asset_name() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let downloads = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = downloads.clone();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[
+                        ("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix"),
+                        (
+                            "autohotkey-debug-1.2.0-x64.zip",
+                            "https://example.com/b.zip",
+                        ),
+                    ],
+                ))
+            })
+            .with_downloader(move |url, dest| {
+                recorded.lock().unwrap().push(url.to_string());
+                fake_extract(url, dest)
+            });
+        let options = InstallOptions::from_config(
+            &serde_json::json!({"assetName": "autohotkey-debug-1.2.0-x64.zip"}),
+            |_| None,
+        )
+        .unwrap();
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(
+            *downloads.lock().unwrap(),
+            vec!["https://example.com/b.zip".to_string()]
+        );
+    }
+
+    #[test]
+    fn ensure_adapter_installed_lists_assets_when_asset_name_is_missing() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[
+                        ("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix"),
+                        ("checksums.txt", "https://example.com/c.txt"),
+                    ],
+                ))
+            })
+            .with_downloader(|_, _| panic!("nothing should be downloaded"));
+        let options =
+            InstallOptions::from_config(&serde_json::json!({"assetName": "adapter.vsix"}), |_| {
+                None
+            })
+            .unwrap();
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("no asset named 'adapter.vsix'"));
+        assert!(err.contains("Available: autohotkey-debug-1.2.0.vsix, checksums.txt"));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_skips_download_when_already_installed() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let versioned_dir = temp_dir.path().join(ADAPTER_NAME).join("autohotkey_1.2.0");
+        fake_extract("", &versioned_dir.to_string_lossy()).unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "1.2.0",
+                    &[("a.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(|_, _| Err("should not download".to_string()));
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_rejects_asset_without_download_url() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| Ok(release("1.2.0", &[("autohotkey-debug-1.2.0.vsix", " ")])))
+            .with_downloader(|_, _| panic!("must not download without a URL"));
+        let options = InstallOptions {
+            cache_discovery_attempts: 1,
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert!(result.unwrap_err().contains("has no download URL"));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_rejects_malformed_asset_name() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger =
+            AutoHotkeyDebugger::with_base_dir(temp_dir.path()).with_release_fetcher(|_| {
+                Ok(release("1.2.0", &[(".vsix", "https://example.com/a.vsix")]))
+            });
+        let options = InstallOptions {
+            cache_discovery_attempts: 1,
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert!(result.unwrap_err().contains("malformed adapter asset name"));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_rejects_version_escaping_adapter_dir() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_dir = temp_dir.path().join("base");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(&base_dir)
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "1.0/../../../escaped",
+                    &[("a.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(|_, _| panic!("must not download outside the adapter dir"));
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert!(result.unwrap_err().contains("Refusing to write"));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_diagnoses_download_failure() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "1.2.0",
+                    &[("a.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(|_, _| Err("HTTP status 404".to_string()));
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("https://example.com/a.vsix"));
+        assert!(err.contains("moved or deleted"));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_rejects_oversized_download() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "1.2.0",
+                    &[("a.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(|url, dest| {
+                fake_extract(url, dest)?;
+                std::fs::write(Path::new(dest).join("padding.bin"), [0u8; 64]).unwrap();
+                Ok(())
+            });
+        let options = InstallOptions {
+            limits: Limits {
+                max_download_size: 16,
+                ..Limits::default()
+            },
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert!(result.unwrap_err().contains("maxDownloadSizeBytes"));
+        assert!(!Path::new(&debugger.versioned_dir("1.2.0")).exists());
+    }
+
+    #[test]
+    fn ensure_adapter_installed_rejects_empty_extraction() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(|_, dest| {
+                std::fs::create_dir_all(Path::new(dest).join("extension")).unwrap();
+                std::fs::write(Path::new(dest).join("extension/package.json"), "").unwrap();
+                Ok(())
+            });
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert!(result.unwrap_err().contains("extracted no files"));
+        assert!(!Path::new(&debugger.versioned_dir("1.2.0")).exists());
+    }
+
+    #[test]
+    fn ensure_adapter_installed_rejects_extraction_without_adapter_script() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(|_, dest| {
+                let path = Path::new(dest).join(AHK_EXE_SUBPATH);
+                write_file(&path);
+                std::fs::write(path, "stub").unwrap();
+                Ok(())
+            });
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert!(result.unwrap_err().contains("has no adapter script"));
+        assert!(!Path::new(&debugger.versioned_dir("1.2.0")).exists());
+    }
+
+    #[test]
+    fn ensure_adapter_installed_accepts_script_at_configured_subpath() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(|_, dest| {
+                let path = Path::new(dest).join("adapter/debugAdapter.ahk");
+                write_file(&path);
+                std::fs::write(path, "stub").unwrap();
+                Ok(())
+            });
+        let options = InstallOptions::from_config(
+            &serde_json::json!({"scriptSubpath": "adapter/debugAdapter.ahk"}),
+            |_| None,
+        )
+        .unwrap();
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_falls_back_to_cache_when_offline() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let versioned_dir = temp_dir.path().join(ADAPTER_NAME).join("autohotkey_1.0.0");
+        fake_extract("", &versioned_dir.to_string_lossy()).unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| Err("network unreachable".to_string()));
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert_eq!(result, Ok("1.0.0".to_string()));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_explains_insecure_downloads_are_unsupported() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://mirror.local/a.vsix")],
+                ))
+            })
+            .with_downloader(|_, _| panic!("insecure downloads must not be attempted"));
+        let options = InstallOptions {
+            allow_insecure_downloads: true,
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("cannot skip TLS certificate checks"));
+        assert!(err.contains("https://mirror.local/a.vsix"));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_cache_first_uses_cache_without_network() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.0.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| panic!("cache-first must not look up a release"));
+        let options = InstallOptions {
+            cache_first: true,
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert_eq!(result, Ok("1.0.0".to_string()));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_cache_first_downloads_without_cache() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(fake_extract);
+        let options = InstallOptions {
+            cache_first: true,
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_network_first_updates_over_cache() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.0.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(fake_extract);
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&InstallOptions::default());
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+    }
+
+    #[test]
+    fn ensure_adapter_installed_errors_when_offline_without_cache() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| Err("network unreachable".to_string()));
+        let options = InstallOptions {
+            cache_discovery_attempts: 1,
+            ..InstallOptions::default()
+        };
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("no cached version found"));
+        assert!(err.contains("network unreachable"));
+    }
+
+    // ==================== install_status tests ====================
+
+    fn install_version(base_dir: &Path, version: &str) {
+        let versioned_dir = base_dir
+            .join(ADAPTER_NAME)
+            .join(format!("{}_{}", ADAPTER_NAME, version));
+        fake_extract("", &versioned_dir.to_string_lossy()).unwrap();
+    }
+
+    #[test]
+    fn install_status_reports_current_install() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| Ok(release("v1.2.0", &[])));
+
+        // Act
+        let result = debugger.install_status();
+
+        // Assert
+        assert_eq!(result.installed_version.as_deref(), Some("1.2.0"));
+        assert!(result.network_reachable);
+        assert!(!result.update_available());
+    }
+
+    #[test]
+    fn install_status_reports_not_installed() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| Ok(release("1.2.0", &[])))
+            .with_downloader(|_, _| panic!("install_status must not download"));
+
+        // Act
+        let result = debugger.install_status();
+
+        // Assert
+        assert!(!result.is_installed());
+        assert_eq!(result.latest_version.as_deref(), Some("1.2.0"));
+        assert!(!result.update_available());
+    }
+
+    #[test]
+    fn install_status_reports_update_available() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.1.0");
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| Ok(release("1.2.0", &[])));
+
+        // Act
+        let result = debugger.install_status();
+
+        // Assert
+        assert_eq!(result.installed_version.as_deref(), Some("1.1.0"));
+        assert!(result.update_available());
+    }
+
+    #[test]
+    fn install_status_reports_cached_only_when_offline() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.1.0");
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| Err("network unreachable".to_string()));
+
+        // Act
+        let result = debugger.install_status();
+
+        // Assert
+        assert_eq!(
+            result,
+            InstallStatus {
+                installed_version: Some("1.1.0".to_string()),
+                latest_version: None,
+                network_reachable: false,
+            }
+        );
+        assert!(!result.update_available());
+    }
+
+    // ==================== cached_versions tests ====================
+
+    #[test]
+    fn cached_versions_sorts_newest_first_by_semver() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let adapter_dir = temp_dir.path().join(ADAPTER_NAME);
+        for name in [
+            "autohotkey_1.2.0",
+            "autohotkey_1.10.0",
+            "autohotkey_1.9.3",
+            "autohotkey_2.0",
+            "autohotkey_latest",
+            "unrelated_1.0.0",
+        ] {
+            std::fs::create_dir_all(adapter_dir.join(name)).unwrap();
+        }
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Act
+        let result = debugger.cached_versions();
+
+        // Assert
+        assert_eq!(result, vec!["2.0", "1.10.0", "1.9.3", "1.2.0"]);
+    }
+
+    #[test]
+    fn cached_versions_is_empty_without_adapter_dir() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Act
+        let result = debugger.cached_versions();
+
+        // Assert
+        assert!(result.is_empty());
+    }
+
+    // ==================== download_url_for tests ====================
+
+    #[test]
+    fn download_url_for_returns_matching_asset_url() {
+        // Arrange
+        let debugger =
+            AutoHotkeyDebugger::with_base_dir("/unused").with_tagged_release_fetcher(|_, tag| {
+                assert_eq!(tag, "v1.1.0");
+                Ok(release(
+                    "v1.1.0",
+                    &[
+                        ("notes.vsix", "https://example.com/notes.vsix"),
+                        ("autohotkey-debug-1.1.0.vsix", "https://example.com/a.vsix"),
+                    ],
+                ))
+            });
+
+        // Act
+        let result = debugger.download_url_for("1.1.0", None);
+
+        // Assert
+        assert_eq!(result.as_deref(), Ok("https://example.com/a.vsix"));
+    }
+
+    #[test]
+    fn download_url_for_reports_unknown_version() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::with_base_dir("/unused")
+            .with_tagged_release_fetcher(|_, _| Err("404 Not Found".to_string()));
+
+        // Act
+        let result = debugger.download_url_for("v9.9.9", None);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("Adapter version 9.9.9 not found"));
+        assert!(err.contains("404 Not Found"));
+    }
+
+    #[test]
+    fn download_url_for_rejects_release_without_vsix() {
+        // Arrange
+        let debugger =
+            AutoHotkeyDebugger::with_base_dir("/unused").with_tagged_release_fetcher(|_, _| {
+                Ok(release(
+                    "v1.1.0",
+                    &[("source.zip", "https://example.com/s.zip")],
+                ))
+            });
+
+        // Act
+        let result = debugger.download_url_for("1.1.0", None);
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .contains("no downloadable .vsix asset (expected autohotkey-debug-1.1.0.vsix)"));
+    }
+
+    // ==================== remove_version tests ====================
+
+    #[test]
+    fn remove_version_deletes_only_that_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.1.0");
+        install_version(temp_dir.path(), "1.2.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        debugger.cached_version.set("1.2.0".to_string()).unwrap();
+
+        // Act
+        let result = debugger.remove_version("1.2.0");
+
+        // Assert
+        assert!(result.is_ok());
+        assert!(!Path::new(&debugger.versioned_dir("1.2.0")).exists());
+        assert!(debugger.adapter_paths("1.1.0").is_complete());
+        assert!(debugger.cached_version.get().is_none());
+    }
+
+    #[test]
+    fn remove_version_keeps_cached_version_when_it_differs() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.1.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        debugger.cached_version.set("1.2.0".to_string()).unwrap();
+
+        // Act
+        let result = debugger.remove_version("1.1.0");
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(
+            debugger.cached_version.get().map(String::as_str),
+            Some("1.2.0")
+        );
+    }
+
+    #[test]
+    fn remove_version_is_noop_when_not_installed() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Act
+        let result = debugger.remove_version("9.9.9");
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn remove_version_rejects_path_like_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Act
+        let result = debugger.remove_version("../other");
+
+        // Assert
+        assert!(result.unwrap_err().contains("Invalid adapter version"));
+    }
+
+    // ==================== cleanup_old_versions tests ====================
+
+    #[test]
+    fn cleanup_old_versions_keeps_newest_versions() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        for version in ["1.0.0", "1.1.0", "1.2.0", "1.3.0"] {
+            install_version(temp_dir.path(), version);
+        }
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Act
+        let result = debugger.cleanup_old_versions("1.3.0", 2);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(debugger.cached_versions(), vec!["1.3.0", "1.2.0"]);
+    }
+
+    #[test]
+    fn cleanup_old_versions_never_removes_current_version() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        for version in ["1.0.0", "2.0.0"] {
+            install_version(temp_dir.path(), version);
+        }
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Act
+        let result = debugger.cleanup_old_versions("1.0.0", 1);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(debugger.cached_versions(), vec!["1.0.0"]);
+    }
+
+    #[test]
+    fn ensure_adapter_installed_succeeds_when_old_version_cannot_be_removed() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let adapter_dir = temp_dir.path().join(ADAPTER_NAME);
+        std::fs::create_dir_all(&adapter_dir).unwrap();
+        // A file where a version directory is expected makes `remove_dir_all` fail
+        std::fs::write(adapter_dir.join(format!("{}_1.0.0", ADAPTER_NAME)), "").unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(fake_extract);
+        let options =
+            InstallOptions::from_config(&serde_json::json!({"maxCachedVersions": 1}), |_| None)
+                .unwrap();
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+        assert_eq!(
+            debugger.cached_version.get().map(String::as_str),
+            Some("1.2.0")
+        );
+        assert!(debugger.adapter_paths("1.2.0").is_complete());
+    }
+
+    #[test]
+    fn ensure_adapter_installed_respects_max_cached_versions() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        for version in ["1.0.0", "1.1.0"] {
+            install_version(temp_dir.path(), version);
+        }
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_release_fetcher(|_| {
+                Ok(release(
+                    "v1.2.0",
+                    &[("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix")],
+                ))
+            })
+            .with_downloader(fake_extract);
+        let options =
+            InstallOptions::from_config(&serde_json::json!({"maxCachedVersions": 2}), |_| None)
+                .unwrap();
+
+        // Act
+        let result = debugger.ensure_adapter_installed(&options);
+
+        // Assert
+        assert_eq!(result, Ok("1.2.0".to_string()));
+        assert_eq!(debugger.cached_versions(), vec!["1.2.0", "1.1.0"]);
+    }
+
+    #[test]
+    fn resolve_config_rejects_max_cached_versions_below_one() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"maxCachedVersions": 0}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'maxCachedVersions'"));
+    }
+
+    #[test]
+    fn install_options_rejects_max_cached_versions_below_one() {
+        // Arrange
+        let config = serde_json::json!({"maxCachedVersions": 0});
+
+        // Act
+        let result = InstallOptions::from_config(&config, |_| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'maxCachedVersions'"));
+    }
+
+    // ==================== repair_install tests ====================
+
+    #[test]
+    fn repair_install_leaves_healthy_install_alone() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_downloader(|_, _| panic!("healthy install must not be downloaded"));
+
+        // Act
+        let result = debugger.repair_install("1.2.0");
+
+        // Assert
+        assert_eq!(result, Ok(false));
+    }
+
+    #[test]
+    fn repair_install_redownloads_corrupted_install() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.1.0");
+        install_version(temp_dir.path(), "1.2.0");
+        let downloads = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = downloads.clone();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_tagged_release_fetcher(tagged_release)
+            .with_downloader(move |url, dest| {
+                recorded.lock().unwrap().push(url.to_string());
+                fake_extract(url, dest)
+            });
+        std::fs::remove_file(debugger.adapter_script_path("1.2.0")).unwrap();
+
+        // Act
+        let result = debugger.repair_install("1.2.0");
+
+        // Assert
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            *downloads.lock().unwrap(),
+            vec!["https://example.com/1.2.0.vsix"]
+        );
+        assert!(debugger.adapter_paths("1.2.0").is_complete());
+        assert!(debugger.adapter_paths("1.1.0").is_complete());
+    }
+
+    #[test]
+    fn repair_install_reports_download_that_stays_incomplete() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_tagged_release_fetcher(tagged_release)
+            .with_downloader(|_, dest| {
+                let path = Path::new(dest).join(ADAPTER_SCRIPT_SUBPATH);
+                write_file(&path);
+                std::fs::write(path, "stub").unwrap();
+                Ok(())
+            });
+
+        // Act
+        let result = debugger.repair_install("1.2.0");
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .contains("Repair of adapter 1.2.0 failed"));
+    }
+
+    #[test]
+    fn reinstall_downloads_configured_asset() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let downloads = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = downloads.clone();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_tagged_release_fetcher(|_, tag| {
+                Ok(release(
+                    tag,
+                    &[
+                        ("autohotkey-debug-1.2.0.vsix", "https://example.com/a.vsix"),
+                        ("adapter-x64.zip", "https://example.com/x64.zip"),
+                    ],
+                ))
+            })
+            .with_downloader(move |url, dest| {
+                recorded.lock().unwrap().push(url.to_string());
+                fake_extract(url, dest)
+            });
+        let options = InstallOptions::from_config(
+            &serde_json::json!({"assetName": "adapter-x64.zip"}),
+            |_| None,
+        )
+        .unwrap();
+
+        // Act
+        let result = debugger.reinstall("1.2.0", &options);
+
+        // Assert
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            *downloads.lock().unwrap(),
+            vec!["https://example.com/x64.zip"]
+        );
+    }
+
+    #[test]
+    fn reinstall_keeps_existing_install_when_download_fails() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_tagged_release_fetcher(tagged_release)
+            .with_downloader(|_, dest| {
+                write_file(&Path::new(dest).join("partial.bin"));
+                Err("connection reset".to_string())
+            });
+
+        // Act
+        let result = debugger.reinstall("1.2.0", &InstallOptions::default());
+
+        // Assert
+        assert!(result.unwrap_err().contains("connection reset"));
+        assert!(debugger.adapter_paths("1.2.0").is_complete());
+        assert!(!Path::new(&format!(
+            "{}{}",
+            debugger.versioned_dir("1.2.0"),
+            STAGING_SUFFIX
+        ))
+        .exists());
+    }
+
+    #[test]
+    fn reinstall_rejects_download_over_size_limit() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_tagged_release_fetcher(tagged_release)
+            .with_downloader(fake_extract);
+        let options =
+            InstallOptions::from_config(&serde_json::json!({"maxDownloadSizeBytes": 1}), |_| None)
+                .unwrap();
+
+        // Act
+        let result = debugger.reinstall("1.2.0", &options);
+
+        // Assert
+        assert!(result.unwrap_err().contains("maxDownloadSizeBytes"));
+        assert!(debugger.adapter_paths("1.2.0").is_complete());
+    }
+
+    // ==================== build_with_repair tests ====================
+
+    #[test]
+    fn build_with_repair_reinstalls_corrupted_install_and_retries() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let downloads = std::sync::Arc::new(AtomicUsize::new(0));
+        let recorded = downloads.clone();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_tagged_release_fetcher(tagged_release)
+            .with_downloader(move |url, dest| {
+                recorded.fetch_add(1, Ordering::SeqCst);
+                fake_extract(url, dest)
+            });
+        std::fs::remove_file(debugger.ahk_exe_path("1.2.0", None)).unwrap();
+
+        // Act
+        let result = debugger.build_with_repair("1.2.0", &InstallOptions::default(), |debugger| {
+            let paths = debugger.adapter_paths("1.2.0");
+            paths
+                .missing_component_message()
+                .map_or(Ok(paths.exe.clone()), |message| {
+                    Err(BuildError::MissingFiles(message))
+                })
+        });
+
+        // Assert
+        assert_eq!(result, Ok(debugger.ahk_exe_path("1.2.0", None)));
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn build_with_repair_returns_error_when_install_is_healthy() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_downloader(|_, _| panic!("healthy install must not be downloaded"));
+        let attempts = AtomicUsize::new(0);
+
+        // Act
+        let result: Result<(), BuildError> =
+            debugger.build_with_repair("1.2.0", &InstallOptions::default(), |_| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err("Working directory not found".into())
+            });
+
+        // Assert
+        assert_eq!(
+            result,
+            Err(BuildError::Invalid("Working directory not found".into()))
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn build_with_repair_keeps_install_on_config_error() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_downloader(|_, _| panic!("a config error must not trigger a download"));
+        let bin_dir = Path::new(&debugger.versioned_dir("1.2.0")).join("extension/bin");
+        std::fs::rename(
+            bin_dir.join("AutoHotkey.exe"),
+            bin_dir.join("AutoHotkey64.exe"),
+        )
+        .unwrap();
+
+        // Act
+        let result = debugger.build_with_repair("1.2.0", &InstallOptions::default(), |debugger| {
+            debugger.resolve_config(r#"{"request": "launch", "trace": "loud"}"#, |_| None)?;
+            Ok(())
+        });
+
+        // Assert
+        assert!(matches!(result, Err(BuildError::Invalid(_))));
+        assert!(bin_dir.join("AutoHotkey64.exe").is_file());
+    }
+
+    #[test]
+    fn build_with_repair_keeps_install_on_wrong_exe_subpath() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        install_version(temp_dir.path(), "1.2.0");
+        let downloads = std::sync::Arc::new(AtomicUsize::new(0));
+        let recorded = downloads.clone();
+        let mut debugger =
+            AutoHotkeyDebugger::with_base_dir(temp_dir.path()).with_downloader(move |url, dest| {
+                recorded.fetch_add(1, Ordering::SeqCst);
+                fake_extract(url, dest)
+            });
+        let config = serde_json::json!({"exeSubpath": "extension/bin/Typo.exe"});
+
+        // Act
+        let result = debugger.build_with_repair("1.2.0", &InstallOptions::default(), |debugger| {
+            debugger.configured_adapter_paths("1.2.0", &config)
+        });
+
+        // Assert
+        assert!(matches!(result, Err(BuildError::Invalid(_))));
+        assert_eq!(downloads.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn build_with_repair_retries_only_once() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path())
+            .with_tagged_release_fetcher(tagged_release)
+            .with_downloader(fake_extract);
+        let attempts = AtomicUsize::new(0);
+
+        // Act
+        let result: Result<(), BuildError> =
+            debugger.build_with_repair("1.2.0", &InstallOptions::default(), |_| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(BuildError::MissingFiles("still broken".into()))
+            });
+
+        // Assert
+        assert_eq!(result, Err(BuildError::MissingFiles("still broken".into())));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    // ==================== Path construction tests ====================
+
+    #[test]
+    fn versioned_dir_contains_version() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let version = "1.2.3";
+
+        // Act
+        let result = debugger.versioned_dir(version);
+
+        // Assert
+        assert!(result.contains("autohotkey_1.2.3"));
+    }
+
+    #[test]
+    fn install_paths_keep_spaces_in_base_dir() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base_dir = temp_dir.path().join("Jane Doe").join("Zed Extensions");
+        let debugger = AutoHotkeyDebugger::with_base_dir(&base_dir);
+        let versioned_dir = base_dir.join(ADAPTER_NAME).join("autohotkey_1.2.3");
+
+        // Act
+        let script = debugger.adapter_script_path("1.2.3");
+        let exe = debugger.ahk_exe_path("1.2.3", None);
+        let arguments = runtime_arguments(&serde_json::json!({}), &script);
+
+        // Assert
+        assert_eq!(
+            Path::new(&script),
+            versioned_dir.join(ADAPTER_SCRIPT_SUBPATH)
+        );
+        assert_eq!(Path::new(&exe), versioned_dir.join(AHK_EXE_SUBPATH));
+        assert_eq!(arguments, vec![script]);
+    }
+
+    #[test]
+    fn adapter_dir_is_shared_without_scope() {
+        // Arrange
+        let first = AutoHotkeyDebugger::new();
+        let second = AutoHotkeyDebugger::new();
+
+        // Act
+        let result = (first.adapter_dir(), second.adapter_dir());
+
+        // Assert
+        assert_eq!(result.0, result.1);
+        assert!(!result.0.contains(WORKTREE_SCOPES_DIR));
+    }
+
+    #[test]
+    fn adapter_dir_differs_between_scoped_worktrees() {
+        // Arrange
+        let mut first = AutoHotkeyDebugger::new();
+        first.install_scope = Some(worktree_scope("C:/projects/alpha"));
+        let mut second = AutoHotkeyDebugger::new();
+        second.install_scope = Some(worktree_scope("C:/projects/beta"));
+
+        // Act
+        let result = (first.adapter_dir(), second.adapter_dir());
+
+        // Assert
+        assert_ne!(result.0, result.1);
+        assert!(result.0.contains(WORKTREE_SCOPES_DIR));
+        assert!(result.0.ends_with(ADAPTER_NAME));
+    }
+
+    #[test]
+    fn worktree_scope_is_stable_for_same_root() {
+        // Arrange
+        let root = "C:/projects/alpha";
+
+        // Act
+        let result = (worktree_scope(root), worktree_scope(root));
+
+        // Assert
+        assert_eq!(result.0, result.1);
+        assert_eq!(result.0.len(), 16);
+    }
+
+    #[test]
+    fn ahk_exe_path_contains_expected_components() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let version = "1.0.0";
+
+        // Act
+        let result = debugger.ahk_exe_path(version, None);
+
+        // Assert
+        assert!(result.contains("extension"));
+        assert!(result.contains("bin"));
+        assert!(result.contains("AutoHotkey.exe"));
+    }
+
+    #[test]
+    fn adapter_paths_detects_install_shipping_only_v2_runtime() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        let versioned_dir = PathBuf::from(debugger.versioned_dir("1.2.0"));
+        write_file(&versioned_dir.join("extension/bin/AutoHotkey64.exe"));
+        write_file(&versioned_dir.join(ADAPTER_SCRIPT_SUBPATH));
+
+        // Act
+        let result = debugger.adapter_paths("1.2.0");
+
+        // Assert
+        assert!(result.exe.ends_with("AutoHotkey64.exe"));
+        assert!(result.is_complete());
+    }
+
+    #[test]
+    fn adapter_script_path_contains_expected_components() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let version = "1.0.0";
+
+        // Act
+        let result = debugger.adapter_script_path(version);
+
+        // Assert
+        assert!(result.contains("extension"));
+        assert!(result.contains("ahkdbg"));
+        assert!(result.contains("debugAdapter.ahk"));
+    }
+
+    #[test]
+    fn configured_adapter_paths_uses_defaults_when_unset() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Act
+        let result = debugger.configured_adapter_paths("1.0.0", &serde_json::json!({}));
+
+        // Assert
+        assert_eq!(result, Ok(debugger.adapter_paths("1.0.0")));
+    }
+
+    #[test]
+    fn configured_adapter_paths_resolves_custom_subpaths() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        let versioned_dir = PathBuf::from(debugger.versioned_dir("1.0.0"));
+        write_file(&versioned_dir.join("runtime/AutoHotkeyU64.exe"));
+        write_file(&versioned_dir.join("adapter/main.ahk"));
+        let config = serde_json::json!({
+            "exeSubpath": "runtime/AutoHotkeyU64.exe",
+            "scriptSubpath": "adapter/main.ahk",
+        });
+
+        // Act
+        let result = debugger.configured_adapter_paths("1.0.0", &config).unwrap();
+
+        // Assert
+        assert_eq!(
+            Path::new(&result.exe),
+            versioned_dir.join("runtime/AutoHotkeyU64.exe")
+        );
+        assert_eq!(
+            Path::new(&result.script),
+            versioned_dir.join("adapter/main.ahk")
+        );
+        assert!(result.exe_exists && result.script_exists);
+    }
+
+    #[test]
+    fn configured_adapter_paths_rejects_missing_custom_subpath() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        let config = serde_json::json!({"scriptSubpath": "adapter/main.ahk"});
+
+        // Act
+        let result = debugger.configured_adapter_paths("1.0.0", &config);
+
+        // Assert
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("'scriptSubpath'"));
+        assert!(err.contains("does not exist"));
+    }
+
+    #[test]
+    fn configured_adapter_paths_rejects_subpath_escaping_install() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        let config = serde_json::json!({"exeSubpath": "../../AutoHotkey.exe"});
+
+        // Act
+        let result = debugger.configured_adapter_paths("1.0.0", &config);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn configured_adapter_paths_skips_checks_with_skip_binary_validation() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        let config = serde_json::json!({
+            "scriptSubpath": "adapter/main.ahk",
+            "skipBinaryValidation": true,
+        });
+
+        // Act
+        let result = debugger.configured_adapter_paths("1.0.0", &config).unwrap();
+
+        // Assert
+        assert!(result.exe_exists);
+        assert!(result.script_exists);
+        assert!(!Path::new(&result.script).exists());
+    }
+
+    #[test]
+    fn configured_adapter_paths_computes_runtime_without_files_when_skipping_validation() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+        let default = serde_json::json!({"skipBinaryValidation": true});
+        let v2 = serde_json::json!({"skipBinaryValidation": true, "runtimeVersion": "v2"});
+
+        // Act
+        let default = debugger
+            .configured_adapter_paths("1.0.0", &default)
+            .unwrap();
+        let v2 = debugger.configured_adapter_paths("1.0.0", &v2).unwrap();
+
+        // Assert
+        assert_eq!(default.exe, debugger.ahk_exe_path("1.0.0", None));
+        assert!(v2.exe.ends_with("AutoHotkey64.exe"));
+        assert!(!Path::new(&default.versioned_dir).exists());
+    }
+
+    #[test]
+    fn configured_adapter_paths_checks_existence_by_default() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let debugger = AutoHotkeyDebugger::with_base_dir(temp_dir.path());
+
+        // Act
+        let result = debugger
+            .configured_adapter_paths("1.0.0", &serde_json::json!({}))
+            .unwrap();
+
+        // Assert
+        assert!(!result.exe_exists);
+        assert!(!result.script_exists);
+    }
+
+    #[test]
+    fn adapter_paths_matches_individual_builders() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let version = "1.0.0";
+
+        // Act
+        let result = debugger.adapter_paths(version);
+
+        // Assert
+        assert_eq!(result.versioned_dir, debugger.versioned_dir(version));
+        assert_eq!(result.exe, debugger.ahk_exe_path(version, None));
+        assert_eq!(result.script, debugger.adapter_script_path(version));
+    }
+
+    // ==================== Filesystem tests using tempfile ====================
+
+    #[test]
+    fn adapter_paths_reports_existence_of_each_component() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let versioned_dir = temp_dir.path().join("autohotkey_1.0.0");
+        let exe = versioned_dir.join(AHK_EXE_SUBPATH);
+        let script = versioned_dir.join(ADAPTER_SCRIPT_SUBPATH);
+        std::fs::create_dir_all(script.parent().unwrap()).unwrap();
+        std::fs::write(&script, "; adapter").unwrap();
+
+        // Act
+        let result = AdapterPaths::new(
+            versioned_dir.to_string_lossy().into_owned(),
+            exe.to_string_lossy().into_owned(),
+            script.to_string_lossy().into_owned(),
+        );
+
+        // Assert
+        assert!(result.versioned_dir_exists);
+        assert!(result.script_exists);
+        assert!(!result.exe_exists);
+    }
+
+    #[test]
+    fn adapter_paths_reports_missing_versioned_dir() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let versioned_dir = temp_dir.path().join("autohotkey_9.9.9");
+        let exe = versioned_dir.join(AHK_EXE_SUBPATH);
+        let script = versioned_dir.join(ADAPTER_SCRIPT_SUBPATH);
+
+        // Act
+        let result = AdapterPaths::new(
+            versioned_dir.to_string_lossy().into_owned(),
+            exe.to_string_lossy().into_owned(),
+            script.to_string_lossy().into_owned(),
+        );
+
+        // Assert
+        assert!(!result.versioned_dir_exists);
+        assert!(!result.exe_exists);
+        assert!(!result.script_exists);
+    }
+
+    fn adapter_paths_in(versioned_dir: &Path) -> AdapterPaths {
+        AdapterPaths::new(
+            versioned_dir.to_string_lossy().into_owned(),
+            versioned_dir
+                .join(AHK_EXE_SUBPATH)
+                .to_string_lossy()
+                .into_owned(),
+            versioned_dir
+                .join(ADAPTER_SCRIPT_SUBPATH)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    fn write_file(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn is_complete_when_sentinel_present() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(INSTALL_SENTINEL));
+
+        // Act
+        let result = adapter_paths_in(temp_dir.path()).is_complete();
+
+        // Assert
+        assert!(result);
+    }
+
+    #[test]
+    fn is_complete_when_sentinel_absent_but_files_present() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(AHK_EXE_SUBPATH));
+        write_file(&temp_dir.path().join(ADAPTER_SCRIPT_SUBPATH));
+
+        // Act
+        let result = adapter_paths_in(temp_dir.path()).is_complete();
+
+        // Assert
+        assert!(result);
+    }
+
+    #[test]
+    fn is_not_complete_when_files_absent() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(ADAPTER_SCRIPT_SUBPATH));
+
+        // Act
+        let result = adapter_paths_in(temp_dir.path()).is_complete();
+
+        // Assert
+        assert!(!result);
+    }
+
+    #[test]
+    fn missing_component_message_is_none_when_install_is_whole() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(AHK_EXE_SUBPATH));
+        write_file(&temp_dir.path().join(ADAPTER_SCRIPT_SUBPATH));
+
+        // Act
+        let result = adapter_paths_in(temp_dir.path()).missing_component_message();
+
+        // Assert
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn missing_component_message_suggests_reinstall_when_dir_missing() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        // Act
+        let result = adapter_paths_in(&temp_dir.path().join("autohotkey_1.0.0"))
+            .missing_component_message()
+            .unwrap();
+
+        // Assert
+        assert!(result.contains("not installed"));
+        assert!(result.contains("reinstalling the extension"));
+    }
+
+    #[test]
+    fn missing_component_message_reports_incomplete_archive_when_exe_missing() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(ADAPTER_SCRIPT_SUBPATH));
+
+        // Act
+        let result = adapter_paths_in(temp_dir.path())
+            .missing_component_message()
+            .unwrap();
+
+        // Assert
+        assert!(result.contains("AutoHotkey.exe not found"));
+        assert!(result.contains("archive was incomplete"));
+        assert!(result.contains("force a fresh download"));
+    }
+
+    #[test]
+    fn missing_component_message_reports_incomplete_archive_when_script_missing() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_file(&temp_dir.path().join(AHK_EXE_SUBPATH));
+
+        // Act
+        let result = adapter_paths_in(temp_dir.path())
+            .missing_component_message()
+            .unwrap();
+
+        // Assert
+        assert!(result.contains("script not found"));
+        assert!(result.contains("archive was incomplete"));
+    }
+
+    #[test]
+    fn missing_component_message_reports_empty_install() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        // Act
+        let result = adapter_paths_in(temp_dir.path())
+            .missing_component_message()
+            .unwrap();
+
+        // Assert
+        assert!(result.contains("empty or corrupt"));
+    }
+
+    #[test]
+    fn write_script_content_writes_temp_script_and_sets_program() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = serde_json::json!({"scriptContent": "MsgBox Hello"});
+
+        // Act
+        let result = write_script_content(&mut config, temp_dir.path());
+
+        // Assert
+        assert!(result.is_ok());
+        let program = config["program"].as_str().unwrap();
+        assert!(program.ends_with(".ahk"));
+        assert!(Path::new(program).starts_with(temp_dir.path()));
+        assert_eq!(std::fs::read_to_string(program).unwrap(), "MsgBox Hello");
+        assert!(config.get("scriptContent").is_none());
+    }
+
+    #[test]
+    fn write_script_content_uses_distinct_directory_per_session() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut first = serde_json::json!({"scriptContent": "MsgBox One"});
+        let mut second = serde_json::json!({"scriptContent": "MsgBox Two"});
+
+        // Act
+        write_script_content(&mut first, temp_dir.path()).unwrap();
+        write_script_content(&mut second, temp_dir.path()).unwrap();
+
+        // Assert
+        let first_dir = Path::new(first["program"].as_str().unwrap())
+            .parent()
+            .unwrap();
+        let second_dir = Path::new(second["program"].as_str().unwrap())
+            .parent()
+            .unwrap();
+        assert_ne!(first_dir, second_dir);
+        assert_eq!(
+            std::fs::read_to_string(first["program"].as_str().unwrap()).unwrap(),
+            "MsgBox One"
+        );
+    }
+
+    #[test]
+    fn create_session_dir_returns_distinct_directories() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        // Act
+        let first = create_session_dir(temp_dir.path(), STALE_SESSION_AGE).unwrap();
+        let second = create_session_dir(temp_dir.path(), STALE_SESSION_AGE).unwrap();
+
+        // Assert
+        assert_ne!(first, second);
+        assert!(first.is_dir());
+        assert!(second.is_dir());
+    }
+
+    #[test]
+    fn cleanup_stale_session_dirs_keeps_recent_and_unrelated_dirs() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let session = create_session_dir(temp_dir.path(), STALE_SESSION_AGE).unwrap();
+        let other = temp_dir.path().join("keep_me");
+        std::fs::create_dir(&other).unwrap();
+
+        // Act
+        cleanup_stale_session_dirs(temp_dir.path(), Duration::from_secs(3600));
+        let kept_while_recent = session.is_dir();
+        cleanup_stale_session_dirs(temp_dir.path(), Duration::ZERO);
+
+        // Assert
+        assert!(kept_while_recent);
+        assert!(!session.exists());
+        assert!(other.is_dir());
+    }
+
+    #[test]
+    fn write_script_content_removes_temp_scripts_older_than_max_age() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut old = serde_json::json!({"scriptContent": "MsgBox Old"});
+        write_script_content(&mut old, temp_dir.path()).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let mut fresh = serde_json::json!({"scriptContent": "MsgBox Fresh"});
+        write_script_content(&mut fresh, temp_dir.path()).unwrap();
+        let mut next = serde_json::json!({
+            "scriptContent": "MsgBox Next",
+            "tempScriptMaxAgeMs": 40,
+        });
+
+        // Act
+        let result = write_script_content(&mut next, temp_dir.path());
+
+        // Assert
+        assert!(result.is_ok());
+        assert!(!Path::new(old["program"].as_str().unwrap()).exists());
+        assert!(Path::new(fresh["program"].as_str().unwrap()).is_file());
+        assert!(Path::new(next["program"].as_str().unwrap()).is_file());
+    }
+
+    #[test]
+    fn write_script_content_rejects_invalid_max_age() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = serde_json::json!({
+            "scriptContent": "MsgBox Hello",
+            "tempScriptMaxAgeMs": 0,
+        });
+
+        // Act
+        let result = write_script_content(&mut config, temp_dir.path());
+
+        // Assert
+        assert!(result.unwrap_err().contains("'tempScriptMaxAgeMs'"));
+    }
+
+    #[test]
+    fn write_script_content_leaves_program_only_config_untouched() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = serde_json::json!({"program": "C:/scripts/main.ahk"});
+
+        // Act
+        let result = write_script_content(&mut config, temp_dir.path());
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["program"], "C:/scripts/main.ahk");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn write_script_content_rejects_program_and_script_content() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = serde_json::json!({
+            "program": "C:/scripts/main.ahk",
+            "scriptContent": "MsgBox Hello",
+        });
+
+        // Act
+        let result = write_script_content(&mut config, temp_dir.path());
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("mutually exclusive"));
+    }
+
+    // ==================== resolve_readiness_probe tests ====================
+
+    #[test]
+    fn resolve_readiness_probe_fills_missing_subfields() {
+        // Arrange
+        let mut config = serde_json::json!({"readinessProbe": {"intervalMs": 250}});
+
+        // Act
+        let result = resolve_readiness_probe(&mut config);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(
+            config["readinessProbe"],
+            serde_json::json!({"intervalMs": 250, "maxAttempts": DEFAULT_PROBE_MAX_ATTEMPTS})
+        );
+    }
+
+    #[test]
+    fn resolve_readiness_probe_leaves_config_without_probe() {
+        // Arrange
+        let mut config = serde_json::json!({});
+
+        // Act
+        let result = resolve_readiness_probe(&mut config);
+
+        // Assert
+        assert!(result.is_ok());
+        assert!(config.get("readinessProbe").is_none());
+    }
+
+    #[test]
+    fn resolve_readiness_probe_rejects_invalid_subfield() {
+        // Arrange
+        let mut config = serde_json::json!({"readinessProbe": {"maxAttempts": 0}});
+
+        // Act
+        let result = resolve_readiness_probe(&mut config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'readinessProbe.maxAttempts'"));
+    }
+
+    #[test]
+    fn resolve_config_injects_readiness_probe() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"readinessProbe": {"intervalMs": 200, "maxAttempts": 10}}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["readinessProbe"]["intervalMs"], 200);
+        assert_eq!(result["readinessProbe"]["maxAttempts"], 10);
+    }
+
+    // ==================== resolve_stop_at tests ====================
+
+    #[test]
+    fn resolve_stop_at_defaults_file_to_program() {
+        // Arrange
+        let mut config = serde_json::json!({"program": "C:/scripts/main.ahk", "stopAtLine": 12});
+
+        // Act
+        let result = resolve_stop_at(&mut config, &|| None);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["stopAtLine"], 12);
+        assert_eq!(config["stopAtFile"], "C:/scripts/main.ahk");
+    }
+
+    #[test]
+    fn resolve_stop_at_keeps_existing_file() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let lib = temp_dir.path().join("lib.ahk");
+        write_file(&lib);
+        let mut config = serde_json::json!({
+            "program": "C:/scripts/main.ahk",
+            "stopAtLine": 3,
+            "stopAtFile": lib.to_string_lossy(),
+        });
+
+        // Act
+        let result = resolve_stop_at(&mut config, &|| None);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["stopAtFile"], lib.to_string_lossy().as_ref());
+    }
+
+    #[test]
+    fn resolve_stop_at_rejects_non_positive_line() {
+        // Arrange
+        let mut config = serde_json::json!({"stopAtLine": 0});
+
+        // Act
+        let result = resolve_stop_at(&mut config, &|| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'stopAtLine'"));
+    }
+
+    #[test]
+    fn resolve_stop_at_rejects_missing_file() {
+        // Arrange
+        let mut config = serde_json::json!({"stopAtLine": 3, "stopAtFile": "/nonexistent/lib.ahk"});
+
+        // Act
+        let result = resolve_stop_at(&mut config, &|| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'stopAtFile' not found"));
+    }
+
+    #[test]
+    fn resolve_stop_at_rejects_file_without_line() {
+        // Arrange
+        let mut config = serde_json::json!({"stopAtFile": "C:/scripts/lib.ahk"});
+
+        // Act
+        let result = resolve_stop_at(&mut config, &|| None);
+
+        // Assert
+        assert!(result.unwrap_err().contains("requires 'stopAtLine'"));
+    }
+
+    // ==================== validate_locale tests ====================
+
+    #[test]
+    fn resolve_config_passes_locale_through() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"locale": "pt-BR"}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(result["locale"], "pt-BR");
+    }
+
+    #[test]
+    fn validate_locale_accepts_language_tags() {
+        for locale in ["de", "en-US", "zh-Hant-TW", "es-419"] {
+            // Arrange
+            let config = serde_json::json!({"locale": locale});
+
+            // Act
+            let result = validate_locale(&config);
+
+            // Assert
+            assert!(result.is_ok(), "expected '{}' to be accepted", locale);
+        }
+    }
+
+    #[test]
+    fn validate_locale_rejects_malformed_tags() {
+        for locale in ["", "english", "en--US", "1a", "en-US.UTF-8"] {
+            // Arrange
+            let config = serde_json::json!({"locale": locale});
+
+            // Act
+            let result = validate_locale(&config);
+
+            // Assert
+            assert!(result.is_err(), "expected '{}' to be rejected", locale);
+        }
+    }
+
+    // ==================== validate_path_mappings tests ====================
+
+    #[test]
+    fn resolve_config_passes_path_mappings_through() {
+        // Arrange
+        let debugger = AutoHotkeyDebugger::new();
+        let config = r#"{"pathMappings": [{"localRoot": "C:/src", "remoteRoot": "D:/deploy"}]}"#;
+
+        // Act
+        let result = debugger.resolve_config(config, |_| None).unwrap();
+
+        // Assert
+        assert_eq!(
+            result["pathMappings"],
+            serde_json::json!([{"localRoot": "C:/src", "remoteRoot": "D:/deploy"}])
+        );
+    }
+
+    #[test]
+    fn validate_path_mappings_rejects_missing_root() {
+        // Arrange
+        let config = serde_json::json!({
+            "pathMappings": [
+                {"localRoot": "C:/src", "remoteRoot": "D:/deploy"},
+                {"localRoot": "C:/lib"},
+            ],
+        });
+
+        // Act
+        let result = validate_path_mappings(&config);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("'pathMappings[1]'"));
+        assert!(err.contains("'remoteRoot'"));
+    }
+
+    #[test]
+    fn validate_path_mappings_rejects_empty_root() {
+        // Arrange
+        let config =
+            serde_json::json!({"pathMappings": [{"localRoot": " ", "remoteRoot": "D:/deploy"}]});
+
+        // Act
+        let result = validate_path_mappings(&config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'localRoot'"));
+    }
+
+    #[test]
+    fn validate_path_mappings_rejects_non_array() {
+        // Arrange
+        let config = serde_json::json!({"pathMappings": {"localRoot": "C:/src"}});
+
+        // Act
+        let result = validate_path_mappings(&config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("expected an array"));
+    }
+
+    // ==================== stopOnEntry fallback tests ====================
+
+    #[test]
+    fn runtime_build_from_path_reads_versioned_directory() {
+        // Act
+        let result = runtime_build_from_path("C:/Tools/AutoHotkey_1.1.33.02/AutoHotkey.exe");
+
+        // Assert
+        assert_eq!(result, Some(vec![1, 1, 33, 2]));
+    }
+
+    #[test]
+    fn runtime_build_from_path_is_none_without_version() {
+        // Act
+        let result = runtime_build_from_path("C:/Program Files/AutoHotkey/AutoHotkey.exe");
+
+        // Assert
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn stop_on_entry_fallback_injects_breakpoint_for_old_runtime() {
+        // Arrange
+        let config = serde_json::json!({"stopOnEntry": true, "stopOnEntryFallback": true});
+
+        // Act
+        let result = stop_on_entry_fallback(&config, Some(&[1, 1, 33, 2]));
+
+        // Assert
+        assert_eq!(result, Ok(StopOnEntryFallback::EntryBreakpoint));
+    }
+
+    #[test]
+    fn stop_on_entry_fallback_not_needed_by_default() {
+        // Arrange
+        let config = serde_json::json!({"stopOnEntry": true});
+
+        // Act
+        let result = stop_on_entry_fallback(&config, Some(&[1, 1, 33, 2]));
+
+        // Assert
+        assert_eq!(result, Ok(StopOnEntryFallback::NotNeeded));
+    }
+
+    #[test]
+    fn stop_on_entry_fallback_not_needed_at_threshold_or_unknown_runtime() {
+        // Arrange
+        let config = serde_json::json!({"stopOnEntry": true});
+
+        // Act
+        let at_threshold = stop_on_entry_fallback(&config, Some(&STOP_ON_ENTRY_MIN_RUNTIME));
+        let unknown = stop_on_entry_fallback(&config, None);
+
+        // Assert
+        assert_eq!(at_threshold, Ok(StopOnEntryFallback::NotNeeded));
+        assert_eq!(unknown, Ok(StopOnEntryFallback::NotNeeded));
+    }
+
+    #[test]
+    fn stop_on_entry_fallback_not_needed_without_stop_on_entry() {
+        // Arrange
+        let config = serde_json::json!({"stopOnEntry": false});
+
+        // Act
+        let result = stop_on_entry_fallback(&config, Some(&[1, 0, 48]));
+
+        // Assert
+        assert_eq!(result, Ok(StopOnEntryFallback::NotNeeded));
+    }
+
+    #[test]
+    fn apply_stop_on_entry_fallback_sets_entry_breakpoint() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "program": "C:/scripts/main.ahk",
+            "stopOnEntry": true,
+            "stopOnEntryFallback": true,
+        });
+
+        // Act
+        let result = apply_stop_on_entry_fallback(
+            &mut config,
+            "C:/Tools/AutoHotkey_1.1.30.00/AutoHotkey.exe",
+            "C:/ext/autohotkey/autohotkey_1.0.0",
+        );
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["stopAtLine"], 1);
+        assert_eq!(config["stopAtFile"], "C:/scripts/main.ahk");
+    }
+
+    #[test]
+    fn apply_stop_on_entry_fallback_ignores_bundled_runtime() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "program": "C:/scripts/main.ahk",
+            "stopOnEntry": true,
+            "stopOnEntryFallback": true,
+        });
+
+        // Act
+        let result = apply_stop_on_entry_fallback(
+            &mut config,
+            "C:/ext/autohotkey/autohotkey_1.0.0/extension/bin/AutoHotkey.exe",
+            "C:/ext/autohotkey/autohotkey_1.0.0",
+        );
+
+        // Assert
+        assert!(result.is_ok());
+        assert!(config.get("stopAtLine").is_none());
+    }
+
+    // ==================== resolve_terminal_title tests ====================
+
+    #[test]
+    fn resolve_terminal_title_defaults_to_label() {
+        // Arrange
+        let mut config = serde_json::json!({});
+
+        // Act
+        let result = resolve_terminal_title(&mut config, "AutoHotkey: main.ahk");
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["terminalTitle"], "AutoHotkey: main.ahk");
+    }
+
+    #[test]
+    fn resolve_terminal_title_keeps_supplied_title() {
+        // Arrange
+        let mut config = serde_json::json!({"terminalTitle": "Hotkeys"});
+
+        // Act
+        let result = resolve_terminal_title(&mut config, "AutoHotkey: main.ahk");
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["terminalTitle"], "Hotkeys");
+    }
+
+    #[test]
+    fn resolve_terminal_title_rejects_non_string() {
+        // Arrange
+        let mut config = serde_json::json!({"terminalTitle": 42});
+
+        // Act
+        let result = resolve_terminal_title(&mut config, "AutoHotkey: main.ahk");
+
+        // Assert
+        assert!(result.unwrap_err().contains("'terminalTitle'"));
+    }
+
+    // ==================== resolve_hit_conditions tests ====================
+
+    #[test]
+    fn resolve_hit_conditions_serializes_entries_with_default_file() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "program": "C:/scripts/main.ahk",
+            "hitConditions": [
+                {"line": 12, "condition": ">= 3"},
+                {"line": 4, "condition": "% 2", "file": "C:/scripts/lib.ahk"},
+            ],
+        });
+
+        // Act
+        let result = resolve_hit_conditions(&mut config);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(
+            config["hitConditions"],
+            serde_json::json!([
+                {"line": 12, "condition": ">= 3", "file": "C:/scripts/main.ahk"},
+                {"line": 4, "condition": "% 2", "file": "C:/scripts/lib.ahk"},
+            ])
+        );
+    }
+
+    #[test]
+    fn resolve_hit_conditions_rejects_missing_condition() {
+        // Arrange
+        let mut config = serde_json::json!({"hitConditions": [{"line": 12}]});
+
+        // Act
+        let result = resolve_hit_conditions(&mut config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'hitConditions[0]'"));
+    }
+
+    #[test]
+    fn resolve_hit_conditions_rejects_invalid_line() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "hitConditions": [{"line": 1, "condition": "> 1"}, {"line": 0, "condition": "> 1"}],
+        });
+
+        // Act
+        let result = resolve_hit_conditions(&mut config);
+
+        // Assert
+        assert!(result.unwrap_err().contains("'hitConditions[1]'"));
+    }
+
+    // ==================== apply_args_file tests ====================
+
+    fn test_home() -> Option<String> {
+        Some("C:/Users/dev".to_string())
+    }
+
+    #[test]
+    fn apply_args_file_reads_one_argument_per_line() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let args_file = temp_dir.path().join("args.txt");
+        std::fs::write(&args_file, "--input\r\n~/data.csv\n\n--verbose\n").unwrap();
+        let mut config = serde_json::json!({"argsFile": args_file.to_string_lossy()});
+
+        // Act
+        let result = apply_args_file(&mut config, "/work/project", &test_home);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(
+            config["args"],
+            serde_json::json!(["--input", "C:/Users/dev/data.csv", "--verbose"])
+        );
+        assert!(config.get("argsFile").is_none());
+    }
+
+    #[test]
+    fn apply_args_file_resolves_path_against_workspace() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workspace = temp_dir.path().to_string_lossy().into_owned();
+        std::fs::create_dir(temp_dir.path().join("config")).unwrap();
+        std::fs::write(
+            temp_dir.path().join("config").join("args.txt"),
+            "--relative",
+        )
+        .unwrap();
+        std::fs::write(temp_dir.path().join("args.txt"), "--workspace").unwrap();
+        let mut relative = serde_json::json!({"argsFile": "config/args.txt"});
+        let mut variable = serde_json::json!({"argsFile": "${workspaceFolder}/args.txt"});
+
+        // Act
+        apply_args_file(&mut relative, &workspace, &test_home).unwrap();
+        apply_args_file(&mut variable, &workspace, &test_home).unwrap();
+
+        // Assert
+        assert_eq!(relative["args"], serde_json::json!(["--relative"]));
+        assert_eq!(variable["args"], serde_json::json!(["--workspace"]));
+    }
+
+    #[test]
+    fn apply_args_file_rejects_args_and_args_file() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let args_file = temp_dir.path().join("args.txt");
+        std::fs::write(&args_file, "--verbose").unwrap();
+        let mut config = serde_json::json!({
+            "args": ["--fast"],
+            "argsFile": args_file.to_string_lossy(),
+        });
+
+        // Act
+        let result = apply_args_file(&mut config, "/work/project", &test_home);
+
+        // Assert
+        assert!(result.unwrap_err().contains("mutually exclusive"));
+    }
+
+    #[test]
+    fn apply_args_file_errors_when_file_is_missing() {
+        // Arrange
+        let mut config = serde_json::json!({"argsFile": "/nonexistent/args.txt"});
+
+        // Act
+        let result = apply_args_file(&mut config, "/work/project", &test_home);
+
+        // Assert
+        assert!(result.unwrap_err().contains("Failed to read 'argsFile'"));
+    }
+
+    #[test]
+    fn dap_config_to_scenario_returns_error_for_missing_program() {
+        // Arrange
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: "/nonexistent/path/script.ahk".to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
+
+        // Act
+        let result = debugger.dap_config_to_scenario(config);
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Script file not found"));
+    }
+
+    #[test]
+    fn dap_config_to_scenario_returns_error_for_attach_mode() {
+        // Arrange
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Attach(AttachRequest { process_id: None }),
+            stop_on_entry: None,
+        };
+
+        // Act
+        let result = debugger.dap_config_to_scenario(config);
+
+        // Assert
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("does not support attach mode"));
+    }
+
+    #[test]
+    fn dap_config_to_scenario_succeeds_with_existing_file() {
+        // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: script_path.to_string_lossy().to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: Some(true),
+        };
 
         // Act
-        let result = request_type_from_config(&config);
+        let result = debugger.dap_config_to_scenario(config);
 
         // Assert
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Invalid request type"));
+        assert!(result.is_ok());
+        let scenario = result.unwrap();
+        assert_eq!(scenario.adapter, "autohotkey");
+        assert!(scenario.config.contains("\"stopOnEntry\": true"));
     }
 
+    #[cfg(unix)]
     #[test]
-    fn request_type_from_config_defaults_to_launch_when_missing() {
+    fn dap_config_to_scenario_keeps_symlinked_program_path() {
         // Arrange
-        let config = serde_json::json!({});
+        let temp_dir = tempfile::tempdir().unwrap();
+        let target = temp_dir.path().join("real.ahk");
+        std::fs::write(&target, "MsgBox Hello").unwrap();
+        let link = temp_dir.path().join("link.ahk");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: link.to_string_lossy().to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = request_type_from_config(&config);
+        let result = AutoHotkeyDebugger::new()
+            .dap_config_to_scenario(config)
+            .unwrap();
 
         // Assert
-        assert!(matches!(
-            result,
-            Ok(StartDebuggingRequestArgumentsRequest::Launch)
-        ));
+        let scenario: serde_json::Value = serde_json::from_str(&result.config).unwrap();
+        assert_eq!(scenario["program"], link.to_string_lossy().as_ref());
     }
 
     #[test]
-    fn request_type_from_config_defaults_to_launch_when_null() {
+    fn dap_config_to_scenario_rejects_placeholder_program_by_default() {
         // Arrange
-        let config = serde_json::json!({"request": null});
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Template".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: "${workspaceFolder}/${SCRIPT}.ahk".to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = request_type_from_config(&config);
+        let result = debugger.dap_config_to_scenario(config);
 
         // Assert
-        assert!(matches!(
-            result,
-            Ok(StartDebuggingRequestArgumentsRequest::Launch)
-        ));
+        assert!(result.unwrap_err().contains("Script file not found"));
     }
 
-    // ==================== validate_adapter_name tests ====================
-
     #[test]
-    fn validate_adapter_name_accepts_autohotkey() {
+    fn dap_config_to_scenario_allows_empty_program_by_default() {
         // Arrange
-        let name = "autohotkey";
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: "".to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = validate_adapter_name(name);
+        let result = debugger.dap_config_to_scenario(config);
 
         // Assert
         assert!(result.is_ok());
     }
 
     #[test]
-    fn validate_adapter_name_rejects_other_names() {
+    fn dap_config_to_scenario_and_resolve_config_share_default_port() {
         // Arrange
-        let name = "python";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+        let program = script_path.to_string_lossy().to_string();
+
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: program.clone(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = validate_adapter_name(name);
+        let scenario = debugger.dap_config_to_scenario(config).unwrap();
+        let resolved = debugger
+            .resolve_config(
+                &serde_json::json!({ "program": program }).to_string(),
+                |_| None,
+            )
+            .unwrap();
 
         // Assert
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Unsupported adapter"));
+        let scenario: serde_json::Value = serde_json::from_str(&scenario.config).unwrap();
+        assert_eq!(scenario["port"], DEFAULT_PORT);
+        assert_eq!(resolved["port"], scenario["port"]);
     }
 
     #[test]
-    fn validate_adapter_name_rejects_empty_string() {
+    fn parse_port_parses_decimal_port() {
         // Arrange
-        let name = "";
+        let value = "9100";
 
         // Act
-        let result = validate_adapter_name(name);
+        let result = parse_port(value);
 
         // Assert
-        assert!(result.is_err());
+        assert_eq!(result, 9100);
     }
 
     #[test]
-    fn validate_adapter_name_is_case_sensitive() {
+    fn dap_config_to_scenario_trims_trailing_carriage_return_from_program() {
         // Arrange
-        let name = "AutoHotkey";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+        let program = script_path.to_string_lossy().to_string();
+
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: format!("{}\r", program),
+                cwd: None,
+                args: vec!["--fast\r".to_string()],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = validate_adapter_name(name);
+        let result = debugger.dap_config_to_scenario(config);
 
         // Assert
-        assert!(result.is_err());
+        let scenario: serde_json::Value = serde_json::from_str(&result.unwrap().config).unwrap();
+        assert_eq!(scenario["program"], program);
+        assert_eq!(scenario["args"], serde_json::json!(["--fast"]));
     }
 
-    // ==================== parse_request_kind tests ====================
-
     #[test]
-    fn parse_request_kind_parses_valid_json() {
+    fn dap_config_to_scenario_fills_in_empty_label() {
         // Arrange
-        let json = r#"{"request": "launch"}"#;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: " ".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: script_path.to_string_lossy().to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = AutoHotkeyDebugger::parse_request_kind(json);
+        let result = debugger.dap_config_to_scenario(config);
 
         // Assert
-        assert!(matches!(
-            result,
-            Ok(StartDebuggingRequestArgumentsRequest::Launch)
-        ));
+        assert_eq!(result.unwrap().label, "AutoHotkey: test.ahk");
     }
 
     #[test]
-    fn parse_request_kind_returns_error_for_invalid_json() {
+    fn dap_config_to_scenario_keeps_provided_label() {
         // Arrange
-        let json = "not valid json";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "My Script".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: script_path.to_string_lossy().to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = AutoHotkeyDebugger::parse_request_kind(json);
+        let result = debugger.dap_config_to_scenario(config);
 
         // Assert
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Failed to parse config JSON"));
+        assert_eq!(result.unwrap().label, "My Script");
     }
 
     #[test]
-    fn parse_request_kind_handles_empty_json_object() {
+    fn dap_config_to_scenario_accepts_v2_script_without_selected_runtime() {
         // Arrange
-        let json = "{}";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "#Requires AutoHotkey v2.0\nMsgBox \"Hello\"").unwrap();
+
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: script_path.to_string_lossy().to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = AutoHotkeyDebugger::parse_request_kind(json);
+        let result = debugger.dap_config_to_scenario(config);
 
         // Assert
-        assert!(matches!(
-            result,
-            Ok(StartDebuggingRequestArgumentsRequest::Launch)
-        ));
+        assert!(result.is_ok());
     }
 
-    // ==================== Path construction tests ====================
+    fn launch_config(program: &Path) -> DebugConfig {
+        DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: program.to_string_lossy().to_string(),
+                cwd: None,
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        }
+    }
 
     #[test]
-    fn versioned_dir_contains_version() {
+    fn dap_config_to_scenario_pretty_prints_config() {
         // Arrange
-        let debugger = AutoHotkeyDebugger::new();
-        let version = "1.2.3";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+        let mut debugger = AutoHotkeyDebugger::new();
 
         // Act
-        let result = debugger.versioned_dir(version);
+        let result = debugger.dap_config_to_scenario(launch_config(&script_path));
 
         // Assert
-        assert!(result.contains("autohotkey_1.2.3"));
+        let pretty = result.unwrap().config;
+        let parsed: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+        let compact = parsed.to_string();
+        assert!(pretty.contains('\n'));
+        assert!(!compact.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&compact).unwrap(),
+            parsed
+        );
     }
 
     #[test]
-    fn ahk_exe_path_contains_expected_components() {
+    fn dap_config_to_scenario_omits_tcp_connection_by_default() {
         // Arrange
-        let debugger = AutoHotkeyDebugger::new();
-        let version = "1.0.0";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+        let mut debugger = AutoHotkeyDebugger::new();
 
         // Act
-        let result = debugger.ahk_exe_path(version);
+        let result = debugger.dap_config_to_scenario(launch_config(&script_path));
 
         // Assert
-        assert!(result.contains("extension"));
-        assert!(result.contains("bin"));
-        assert!(result.contains("AutoHotkey.exe"));
+        assert!(result.unwrap().tcp_connection.is_none());
     }
 
     #[test]
-    fn adapter_script_path_contains_expected_components() {
+    fn dap_config_to_scenario_resolves_file_dirname_cwd() {
         // Arrange
-        let debugger = AutoHotkeyDebugger::new();
-        let version = "1.0.0";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+
+        let mut debugger = AutoHotkeyDebugger::new();
+        let config = DebugConfig {
+            adapter: "autohotkey".to_string(),
+            label: "Test".to_string(),
+            request: DebugRequest::Launch(LaunchRequest {
+                program: script_path.to_string_lossy().to_string(),
+                cwd: Some("${fileDirname}".to_string()),
+                args: vec![],
+                envs: vec![],
+            }),
+            stop_on_entry: None,
+        };
 
         // Act
-        let result = debugger.adapter_script_path(version);
+        let result = debugger.dap_config_to_scenario(config);
 
         // Assert
-        assert!(result.contains("extension"));
-        assert!(result.contains("ahkdbg"));
-        assert!(result.contains("debugAdapter.ahk"));
+        let scenario: serde_json::Value = serde_json::from_str(&result.unwrap().config).unwrap();
+        assert_eq!(
+            scenario["cwd"].as_str().unwrap(),
+            temp_dir.path().to_string_lossy()
+        );
     }
 
-    // ==================== Filesystem tests using tempfile ====================
-
     #[test]
-    fn dap_config_to_scenario_returns_error_for_missing_program() {
+    fn export_scenario_round_trips_key_fields() {
         // Arrange
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script_path = temp_dir.path().join("test.ahk");
+        std::fs::write(&script_path, "MsgBox Hello").unwrap();
+        let program = script_path.to_string_lossy().to_string();
+
         let mut debugger = AutoHotkeyDebugger::new();
         let config = DebugConfig {
             adapter: "autohotkey".to_string(),
-            label: "Test".to_string(),
+            label: "Shared".to_string(),
             request: DebugRequest::Launch(LaunchRequest {
-                program: "/nonexistent/path/script.ahk".to_string(),
+                program: program.clone(),
                 cwd: None,
-                args: vec![],
+                args: vec!["--fast".to_string()],
                 envs: vec![],
             }),
-            stop_on_entry: None,
+            stop_on_entry: Some(true),
         };
 
         // Act
-        let result = debugger.dap_config_to_scenario(config);
+        let result = debugger.export_scenario(config).unwrap();
 
         // Assert
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Script file not found"));
+        let exported: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(exported["label"], "Shared");
+        assert_eq!(exported["adapter"], "autohotkey");
+        assert_eq!(exported["request"], "launch");
+        assert_eq!(exported["program"], program);
+        assert_eq!(exported["args"], serde_json::json!(["--fast"]));
+        assert_eq!(exported["stopOnEntry"], true);
+        assert!(result.contains('\n'));
     }
 
     #[test]
-    fn dap_config_to_scenario_returns_error_for_attach_mode() {
+    fn export_scenario_propagates_attach_error() {
         // Arrange
         let mut debugger = AutoHotkeyDebugger::new();
         let config = DebugConfig {
@@ -530,16 +8900,18 @@ mod tests {
         };
 
         // Act
-        let result = debugger.dap_config_to_scenario(config);
+        let result = debugger.export_scenario(config);
 
         // Assert
-        assert!(result.is_err());
         assert!(result.unwrap_err().contains("does not support attach mode"));
     }
 
     #[test]
-    fn dap_config_to_scenario_succeeds_with_existing_file() {
+    fn dap_config_to_scenario_expands_user_home_in_cwd_and_args() {
         // Arrange
+        let Some(home) = user_home() else {
+            return;
+        };
         let temp_dir = tempfile::tempdir().unwrap();
         let script_path = temp_dir.path().join("test.ahk");
         std::fs::write(&script_path, "MsgBox Hello").unwrap();
@@ -550,21 +8922,23 @@ mod tests {
             label: "Test".to_string(),
             request: DebugRequest::Launch(LaunchRequest {
                 program: script_path.to_string_lossy().to_string(),
-                cwd: None,
-                args: vec![],
+                cwd: Some("${userHome}/scripts".to_string()),
+                args: vec!["~/out.log".to_string()],
                 envs: vec![],
             }),
-            stop_on_entry: Some(true),
+            stop_on_entry: None,
         };
 
         // Act
         let result = debugger.dap_config_to_scenario(config);
 
         // Assert
-        assert!(result.is_ok());
-        let scenario = result.unwrap();
-        assert_eq!(scenario.adapter, "autohotkey");
-        assert!(scenario.config.contains("\"stopOnEntry\":true"));
+        let scenario: serde_json::Value = serde_json::from_str(&result.unwrap().config).unwrap();
+        assert_eq!(scenario["cwd"], format!("{}/scripts", home));
+        assert_eq!(
+            scenario["args"],
+            serde_json::json!([format!("{}/out.log", home)])
+        );
     }
 
     #[test]
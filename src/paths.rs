@@ -0,0 +1,513 @@
+//! Helpers for expanding and normalizing paths from the debug configuration.
+
+use std::path::{Component, Path, PathBuf};
+
+use zed_extension_api::serde_json::Value;
+
+const USER_HOME_VAR: &str = "${userHome}";
+/// Config fields that name a single directory.
+const DIRECTORY_FIELDS: [&str; 3] = ["cwd", "libPath", "dataDir"];
+
+/// Returns the user's home directory, preferring `USERPROFILE` (Windows) over `HOME`.
+pub(crate) fn home_dir(env_lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    ["USERPROFILE", "HOME"]
+        .iter()
+        .find_map(|name| env_lookup(name).filter(|home| !home.is_empty()))
+}
+
+/// Expands `${userHome}` and a leading `~` in `value`. `home` is only called when the
+/// value actually references the home directory.
+pub(crate) fn expand_home(
+    value: &str,
+    home: &dyn Fn() -> Option<String>,
+) -> Result<String, String> {
+    let has_tilde = value == "~" || value.starts_with("~/") || value.starts_with("~\\");
+    if !has_tilde && !value.contains(USER_HOME_VAR) {
+        return Ok(value.to_owned());
+    }
+
+    let home = home().ok_or_else(|| {
+        format!(
+            "Cannot expand '{}': the home directory could not be determined. Set HOME or USERPROFILE, or use an absolute path.",
+            value
+        )
+    })?;
+
+    let expanded = match value.strip_prefix('~') {
+        Some(rest) if has_tilde => format!("{}{}", home, rest),
+        _ => value.to_owned(),
+    };
+    Ok(expanded.replace(USER_HOME_VAR, &home))
+}
+
+/// Removes trailing whitespace and control characters, such as a stray `\r` left by a
+/// CRLF line ending, which would otherwise make existence checks fail.
+pub(crate) fn trim_trailing(value: &str) -> &str {
+    value.trim_end_matches(|c: char| c.is_whitespace() || c.is_control())
+}
+
+/// Applies [`trim_trailing`] to `program`, `cwd`, each `args` entry, and each `env` value.
+pub(crate) fn trim_trailing_in_config(config: &mut Value) {
+    let trim = |value: &mut Value| {
+        if let Value::String(s) = value {
+            let trimmed_len = trim_trailing(s).len();
+            s.truncate(trimmed_len);
+        }
+    };
+
+    for field in ["program", "cwd"] {
+        if let Some(value) = config.get_mut(field) {
+            trim(value);
+        }
+    }
+    if let Some(Value::Array(args)) = config.get_mut("args") {
+        args.iter_mut().for_each(trim);
+    }
+    if let Some(Value::Object(env)) = config.get_mut("env") {
+        env.values_mut().for_each(trim);
+    }
+}
+
+/// Removes trailing `/` and `\` separators, keeping those that make up a root such as
+/// `/` or `C:\`, so a directory compares and joins the same with or without them.
+pub(crate) fn trim_trailing_separators(value: &str) -> &str {
+    let trimmed = value.trim_end_matches(['/', '\\']);
+    if trimmed.len() < value.len() && (trimmed.is_empty() || trimmed.ends_with(':')) {
+        // Keep the root's own separator
+        &value[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+/// Applies [`trim_trailing_separators`] to the directory fields of `config` and to each
+/// `programSearchPaths` entry.
+pub(crate) fn trim_separators_in_config(config: &mut Value) {
+    let trim = |value: &mut Value| {
+        if let Value::String(s) = value {
+            let trimmed_len = trim_trailing_separators(s).len();
+            s.truncate(trimmed_len);
+        }
+    };
+
+    for field in DIRECTORY_FIELDS {
+        if let Some(value) = config.get_mut(field) {
+            trim(value);
+        }
+    }
+    if let Some(Value::Array(dirs)) = config.get_mut("programSearchPaths") {
+        dirs.iter_mut().for_each(trim);
+    }
+}
+
+/// Expands home references in the `program`, `cwd`, and `args` fields of `config`.
+pub(crate) fn expand_home_in_config(
+    config: &mut Value,
+    home: &dyn Fn() -> Option<String>,
+) -> Result<(), String> {
+    for field in ["program", "cwd"] {
+        if let Some(Value::String(value)) = config.get_mut(field) {
+            *value = expand_home(value, home)?;
+        }
+    }
+
+    if let Some(Value::Array(args)) = config.get_mut("args") {
+        for arg in args.iter_mut() {
+            if let Value::String(value) = arg {
+                *value = expand_home(value, home)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes `.` and `..` segments without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Resolves `path` against `base` and rejects it if it escapes `base`, e.g. through
+/// `..` segments. The check is lexical, so neither path has to exist yet.
+pub(crate) fn ensure_within(base: &Path, path: &Path) -> Result<PathBuf, String> {
+    let resolved = normalize(&base.join(path));
+    if resolved.starts_with(normalize(base)) {
+        Ok(resolved)
+    } else {
+        Err(format!(
+            "Refusing to write to '{}': it is outside '{}'",
+            path.display(),
+            base.display()
+        ))
+    }
+}
+
+/// Resolves a relative `program` that doesn't exist as given against the directories in
+/// `programSearchPaths`, replacing it with the first match.
+pub(crate) fn resolve_program_in_search_paths(
+    config: &mut Value,
+    home: &dyn Fn() -> Option<String>,
+) -> Result<(), String> {
+    let Some(search_paths) = config.get("programSearchPaths") else {
+        return Ok(());
+    };
+    let search_paths = search_paths
+        .as_array()
+        .and_then(|dirs| {
+            dirs.iter()
+                .map(|dir| dir.as_str())
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or("'programSearchPaths' must be an array of directory paths")?
+        .into_iter()
+        .map(|dir| expand_home(trim_trailing(dir), home))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let program = match config.get("program").and_then(|v| v.as_str()) {
+        Some(program) if !program.is_empty() => program,
+        _ => return Ok(()),
+    };
+    if Path::new(program).is_absolute() || Path::new(program).is_file() {
+        return Ok(());
+    }
+
+    match search_paths
+        .iter()
+        .map(|dir| Path::new(dir).join(program))
+        .find(|candidate| candidate.is_file())
+    {
+        Some(found) => {
+            config["program"] = Value::String(found.to_string_lossy().into_owned());
+            Ok(())
+        }
+        None => Err(format!(
+            "Script '{}' not found in 'programSearchPaths'. Searched: {}",
+            program,
+            search_paths.join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zed_extension_api::serde_json;
+
+    fn home() -> Option<String> {
+        Some("C:/Users/dev".to_string())
+    }
+
+    fn no_home() -> Option<String> {
+        None
+    }
+
+    // ==================== home_dir tests ====================
+
+    #[test]
+    fn home_dir_prefers_userprofile() {
+        // Arrange
+        let env_lookup = |name: &str| match name {
+            "USERPROFILE" => Some("C:/Users/dev".to_string()),
+            "HOME" => Some("/home/dev".to_string()),
+            _ => None,
+        };
+
+        // Act
+        let result = home_dir(env_lookup);
+
+        // Assert
+        assert_eq!(result.as_deref(), Some("C:/Users/dev"));
+    }
+
+    #[test]
+    fn home_dir_falls_back_to_home_when_userprofile_is_empty() {
+        // Arrange
+        let env_lookup = |name: &str| match name {
+            "USERPROFILE" => Some(String::new()),
+            "HOME" => Some("/home/dev".to_string()),
+            _ => None,
+        };
+
+        // Act
+        let result = home_dir(env_lookup);
+
+        // Assert
+        assert_eq!(result.as_deref(), Some("/home/dev"));
+    }
+
+    #[test]
+    fn home_dir_ignores_empty_values() {
+        // Arrange
+        let env_lookup = |_: &str| Some(String::new());
+
+        // Act
+        let result = home_dir(env_lookup);
+
+        // Assert
+        assert!(result.is_none());
+    }
+
+    // ==================== trim_trailing tests ====================
+
+    #[test]
+    fn trim_trailing_removes_carriage_return_and_whitespace() {
+        // Arrange
+        let value = "C:/scripts/main.ahk\r\n \t";
+
+        // Act
+        let result = trim_trailing(value);
+
+        // Assert
+        assert_eq!(result, "C:/scripts/main.ahk");
+    }
+
+    #[test]
+    fn trim_trailing_in_config_trims_paths_args_and_env() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "program": "C:/scripts/main.ahk\r",
+            "cwd": "C:/scripts \r",
+            "args": ["--fast\r"],
+            "env": {"MODE": "debug\r"},
+            "label": "Keep me \r",
+        });
+
+        // Act
+        trim_trailing_in_config(&mut config);
+
+        // Assert
+        assert_eq!(config["program"], "C:/scripts/main.ahk");
+        assert_eq!(config["cwd"], "C:/scripts");
+        assert_eq!(config["args"], serde_json::json!(["--fast"]));
+        assert_eq!(config["env"]["MODE"], "debug");
+        assert_eq!(config["label"], "Keep me \r");
+    }
+
+    // ==================== trim_trailing_separators tests ====================
+
+    #[test]
+    fn trim_trailing_separators_normalizes_directories_consistently() {
+        // Arrange
+        let values = ["C:/scripts", "C:/scripts/", "C:/scripts\\", "C:/scripts//"];
+
+        // Act
+        let result = values.map(trim_trailing_separators);
+
+        // Assert
+        assert_eq!(result, ["C:/scripts"; 4]);
+    }
+
+    #[test]
+    fn trim_trailing_separators_keeps_roots() {
+        // Arrange
+        let values = ["/", "C:\\", "C:/", "//"];
+
+        // Act
+        let result = values.map(trim_trailing_separators);
+
+        // Assert
+        assert_eq!(result, ["/", "C:\\", "C:/", "/"]);
+    }
+
+    #[test]
+    fn trim_separators_in_config_trims_directory_fields_only() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "cwd": "C:/scripts/",
+            "libPath": "C:/lib\\",
+            "programSearchPaths": ["C:/shared/", "/"],
+            "program": "C:/scripts/",
+        });
+
+        // Act
+        trim_separators_in_config(&mut config);
+
+        // Assert
+        assert_eq!(config["cwd"], "C:/scripts");
+        assert_eq!(config["libPath"], "C:/lib");
+        assert_eq!(
+            config["programSearchPaths"],
+            serde_json::json!(["C:/shared", "/"])
+        );
+        assert_eq!(config["program"], "C:/scripts/");
+    }
+
+    // ==================== ensure_within tests ====================
+
+    #[test]
+    fn ensure_within_accepts_safe_relative_path() {
+        // Arrange
+        let base = Path::new("/work/project");
+
+        // Act
+        let result = ensure_within(base, Path::new("logs/./../out/debug.log"));
+
+        // Assert
+        assert_eq!(result, Ok(PathBuf::from("/work/project/out/debug.log")));
+    }
+
+    #[test]
+    fn ensure_within_rejects_traversal() {
+        // Arrange
+        let base = Path::new("/work/project");
+
+        // Act
+        let result = ensure_within(base, Path::new("../../etc/passwd"));
+
+        // Assert
+        assert!(result.unwrap_err().contains("outside '/work/project'"));
+    }
+
+    #[test]
+    fn ensure_within_rejects_absolute_path_elsewhere() {
+        // Arrange
+        let base = Path::new("/work/project");
+
+        // Act
+        let result = ensure_within(base, Path::new("/tmp/x.ahk"));
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    // ==================== resolve_program_in_search_paths tests ====================
+
+    #[test]
+    fn resolve_program_in_search_paths_uses_first_match() {
+        // Arrange
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        std::fs::write(second.path().join("tool.ahk"), "").unwrap();
+        let mut config = serde_json::json!({
+            "program": "tool.ahk",
+            "programSearchPaths": [first.path().to_string_lossy(), second.path().to_string_lossy()],
+        });
+
+        // Act
+        let result = resolve_program_in_search_paths(&mut config, &home);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(
+            config["program"],
+            second.path().join("tool.ahk").to_string_lossy().as_ref()
+        );
+    }
+
+    #[test]
+    fn resolve_program_in_search_paths_lists_searched_directories() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "program": "tool.ahk",
+            "programSearchPaths": ["~/scripts", "C:/shared"],
+        });
+
+        // Act
+        let result = resolve_program_in_search_paths(&mut config, &home);
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("'tool.ahk' not found"));
+        assert!(err.contains("C:/Users/dev/scripts, C:/shared"));
+    }
+
+    #[test]
+    fn resolve_program_in_search_paths_ignores_absolute_program() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "program": "/nonexistent/tool.ahk",
+            "programSearchPaths": ["C:/shared"],
+        });
+
+        // Act
+        let result = resolve_program_in_search_paths(&mut config, &home);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["program"], "/nonexistent/tool.ahk");
+    }
+
+    // ==================== expand_home tests ====================
+
+    #[test]
+    fn expand_home_replaces_user_home_variable() {
+        // Arrange
+        let value = "${userHome}/scripts/x.ahk";
+
+        // Act
+        let result = expand_home(value, &home);
+
+        // Assert
+        assert_eq!(result.as_deref(), Ok("C:/Users/dev/scripts/x.ahk"));
+    }
+
+    #[test]
+    fn expand_home_replaces_leading_tilde() {
+        // Arrange
+        let value = "~/scripts/x.ahk";
+
+        // Act
+        let result = expand_home(value, &home);
+
+        // Assert
+        assert_eq!(result.as_deref(), Ok("C:/Users/dev/scripts/x.ahk"));
+    }
+
+    #[test]
+    fn expand_home_leaves_other_tildes_alone() {
+        // Arrange
+        let value = "C:/PROGRA~1/x.ahk";
+
+        // Act
+        let result = expand_home(value, &no_home);
+
+        // Assert
+        assert_eq!(result.as_deref(), Ok("C:/PROGRA~1/x.ahk"));
+    }
+
+    #[test]
+    fn expand_home_errors_when_home_is_unknown() {
+        // Arrange
+        let value = "~/scripts/x.ahk";
+
+        // Act
+        let result = expand_home(value, &no_home);
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .contains("home directory could not be determined"));
+    }
+
+    #[test]
+    fn expand_home_in_config_expands_program_cwd_and_args() {
+        // Arrange
+        let mut config = serde_json::json!({
+            "program": "~/scripts/x.ahk",
+            "cwd": "${userHome}/scripts",
+            "args": ["--log", "~/x.log"],
+        });
+
+        // Act
+        let result = expand_home_in_config(&mut config, &home);
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(config["program"], "C:/Users/dev/scripts/x.ahk");
+        assert_eq!(config["cwd"], "C:/Users/dev/scripts");
+        assert_eq!(
+            config["args"],
+            serde_json::json!(["--log", "C:/Users/dev/x.log"])
+        );
+    }
+}
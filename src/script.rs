@@ -0,0 +1,127 @@
+//! Lightweight inspection of AutoHotkey script sources.
+
+/// Only the top of a script is scanned; `#Requires` is conventionally the first line.
+const DIRECTIVE_SCAN_LINES: usize = 30;
+const REQUIRES_DIRECTIVE: &str = "#requires autohotkey";
+
+/// Returns the major AutoHotkey version named by a `#Requires AutoHotkey vN` directive
+/// near the top of `source`, if there is one.
+pub(crate) fn required_major_version(source: &str) -> Option<u32> {
+    source
+        .lines()
+        .take(DIRECTIVE_SCAN_LINES)
+        .map(str::trim)
+        .find_map(|line| {
+            let rest = line
+                .get(..REQUIRES_DIRECTIVE.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(REQUIRES_DIRECTIVE))
+                .map(|_| &line[REQUIRES_DIRECTIVE.len()..])?;
+            let version = rest.trim_start().trim_start_matches(['>', '<', '=']);
+            let digits = version.strip_prefix(['v', 'V']).unwrap_or(version);
+            let major: String = digits.chars().take_while(char::is_ascii_digit).collect();
+            major.parse().ok()
+        })
+}
+
+/// Errors when `source` declares a different major version than `runtime_version`
+/// (`"v1"` or `"v2"`). Scripts without a directive are not checked.
+pub(crate) fn check_runtime_version(
+    program: &str,
+    source: &str,
+    runtime_version: &str,
+) -> Result<(), String> {
+    let Some(required) = required_major_version(source) else {
+        return Ok(());
+    };
+    let selected = runtime_version.trim_start_matches('v');
+    if selected == required.to_string() {
+        return Ok(());
+    }
+    Err(format!(
+        "'{}' requires AutoHotkey v{}, but the selected runtime is {}. Set 'runtimeVersion' and a matching 'runtimeExecutable', or update the script's #Requires directive.",
+        program, required, runtime_version
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ==================== required_major_version tests ====================
+
+    #[test]
+    fn required_major_version_reads_directive() {
+        // Arrange
+        let source = "; My script\n#Requires AutoHotkey v2.0\nMsgBox \"Hi\"";
+
+        // Act
+        let result = required_major_version(source);
+
+        // Assert
+        assert_eq!(result, Some(2));
+    }
+
+    #[test]
+    fn required_major_version_handles_comparison_and_case() {
+        // Arrange
+        let source = "#requires autohotkey >=v1.1.33+ 64-bit";
+
+        // Act
+        let result = required_major_version(source);
+
+        // Assert
+        assert_eq!(result, Some(1));
+    }
+
+    #[test]
+    fn required_major_version_is_none_without_directive() {
+        // Arrange
+        let source = "#NoEnv\nMsgBox Hello";
+
+        // Act
+        let result = required_major_version(source);
+
+        // Assert
+        assert_eq!(result, None);
+    }
+
+    // ==================== check_runtime_version tests ====================
+
+    #[test]
+    fn check_runtime_version_accepts_matching_directive() {
+        // Arrange
+        let source = "#Requires AutoHotkey v1.1";
+
+        // Act
+        let result = check_runtime_version("main.ahk", source, "v1");
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn check_runtime_version_rejects_conflicting_directive() {
+        // Arrange
+        let source = "#Requires AutoHotkey v2.0";
+
+        // Act
+        let result = check_runtime_version("main.ahk", source, "v1");
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(err.contains("requires AutoHotkey v2"));
+        assert!(err.contains("selected runtime is v1"));
+    }
+
+    #[test]
+    fn check_runtime_version_skips_scripts_without_directive() {
+        // Arrange
+        let source = "MsgBox Hello";
+
+        // Act
+        let result = check_runtime_version("main.ahk", source, "v2");
+
+        // Assert
+        assert!(result.is_ok());
+    }
+}